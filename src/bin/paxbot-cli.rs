@@ -0,0 +1,107 @@
+//! paxbot-cli searches the tip database from a terminal, without Discord. It runs a single search when given a query
+//! as arguments, and otherwise reads queries interactively. Accepts the same `--config` flag as the bot, and `--json`
+//! to print single searches as JSON snapshots.
+
+use std::io::{self, BufRead, Write};
+
+use paxbot::config::{self, Config};
+use paxbot::render::{json::JsonRenderer, text::TextRenderer, RenderableResponse};
+use paxbot::search::{
+    backend::{build_search_backend, SearchBackend},
+    search,
+};
+
+#[tokio::main]
+async fn main() {
+    let (config_path, mut args) = match config::parse_args() {
+        Ok(parsed) => parsed,
+        Err(why) => {
+            eprintln!("{}", why);
+            std::process::exit(2);
+        }
+    };
+    match Config::load(config_path.as_deref()) {
+        Ok(loaded) => config::init(loaded),
+        Err(why) => {
+            eprintln!("{}", why);
+            std::process::exit(1);
+        }
+    }
+    let mut search_backend = match build_search_backend() {
+        Ok(backend) => backend,
+        Err(why) => {
+            eprintln!("Could not load search data: {}", why);
+            std::process::exit(1);
+        }
+    };
+    let as_json = args.first().map(String::as_str) == Some("--json");
+    if as_json {
+        args.remove(0);
+    }
+    if !args.is_empty() {
+        let search_response = search(&args.join(" "), search_backend.as_ref()).await;
+        let renderable_response = search_response.get_renderable_response();
+        if as_json {
+            let snapshot = renderable_response.render_with(&JsonRenderer);
+            match serde_json::to_string_pretty(&snapshot) {
+                Ok(snapshot) => println!("{}", snapshot),
+                Err(err) => eprintln!("Failed to format the response as JSON: {}", err),
+            }
+        } else {
+            print_response(&renderable_response);
+        }
+        return;
+    }
+    run_repl(search_backend.as_mut()).await;
+}
+
+/// Reads queries from stdin until it closes or `:quit` is entered, printing the response to each.
+async fn run_repl(search_backend: &mut dyn SearchBackend) {
+    println!("Enter a query to search, :reload to reload content, or :quit to exit.");
+    let stdin = io::stdin();
+    let mut line = String::new();
+    loop {
+        print!("> ");
+        if let Err(err) = io::stdout().flush() {
+            eprintln!("Failed to write to stdout: {}", err);
+            return;
+        }
+        line.clear();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => return,
+            Ok(_) => (),
+            Err(err) => {
+                eprintln!("Failed to read from stdin: {}", err);
+                return;
+            }
+        }
+        match line.trim() {
+            "" => continue,
+            ":q" | ":quit" => return,
+            ":reload" => match search_backend.reload() {
+                Ok(()) => println!(
+                    "Reloaded {} categories and {} search results.",
+                    search_backend.data().categories.len(),
+                    search_backend.data().search_results.len()
+                ),
+                Err(why) => eprintln!("Failed to reload search data: {}", why),
+            },
+            query => {
+                let search_response = search(query, search_backend).await;
+                print_response(&search_response.get_renderable_response());
+            }
+        }
+    }
+}
+
+/// Prints every page of a response as plain text.
+fn print_response(response: &RenderableResponse) {
+    let pages = response.render_with(&TextRenderer);
+    let total_len = pages.len();
+    for (index, page) in pages.iter().enumerate() {
+        if total_len > 1 {
+            println!("--- {} of {} ---", index + 1, total_len);
+        }
+        println!("{}\n", page);
+    }
+}
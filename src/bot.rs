@@ -0,0 +1,426 @@
+//! This file implements the Discord bot: the command framework, event handling and startup.
+
+use std::{collections::HashSet, sync::Arc, time::Instant};
+
+use serenity::prelude::*;
+use serenity::{
+    async_trait,
+    builder::{CreateInteractionResponse, CreateInteractionResponseMessage},
+    framework::standard::{macros::hook, CommandResult, Configuration, StandardFramework},
+    gateway::ShardManager,
+    http::Http,
+    model::{
+        application::{ComponentInteraction, Interaction},
+        channel::{Message, Reaction, ReactionType},
+        gateway::{GatewayIntents, Ready},
+        id::{GuildId, UserId},
+        permissions::Permissions,
+    },
+};
+use tokio::sync::{Mutex, RwLock};
+
+use crate::commands::ask::CMDASK_GROUP;
+use crate::commands::slash;
+use crate::commands::util::{guild_prefix, CMDUTIL_GROUP};
+use crate::config::config;
+use crate::render::{
+    build_response_map, expire_responses, restore_responses, Navigation, RenderableResponse, RenderableResponseKey,
+};
+use crate::search::backend::{build_search_backend, SearchDataKey};
+use crate::search::reload::watch_search_backend;
+use crate::store::{feedback::Feedback, Store, StoreKey};
+
+pub struct ShardManagerContainer;
+impl TypeMapKey for ShardManagerContainer {
+    type Value = Arc<ShardManager>;
+}
+
+/// When paxbot started, for reporting uptime.
+pub struct StartTimeKey;
+impl TypeMapKey for StartTimeKey {
+    type Value = Instant;
+}
+
+/// Counts every command run, and the user running it, for `?!pax about`.
+#[hook]
+async fn before(ctx: &Context, msg: &Message, command_name: &str) -> bool {
+    let store = {
+        let ctx_data = ctx.data.read().await;
+        ctx_data.get::<StoreKey>().expect("State store missing.").clone()
+    };
+    let record_result = store.lock().await.record_command(command_name, msg.author.id);
+    if let Err(err) = record_result {
+        eprintln!("Failed to count a command. {}", err);
+    }
+    true
+}
+
+/// Resolves the command prefix for the guild a message was sent in.
+#[hook]
+async fn dynamic_prefix(ctx: &Context, msg: &Message) -> Option<String> {
+    Some(guild_prefix(ctx, msg.guild_id).await)
+}
+
+/// Logs and counts failed commands.
+#[hook]
+async fn after(ctx: &Context, _: &Message, command_name: &str, command_result: CommandResult) {
+    if let Err(why) = command_result {
+        eprintln!("Command {} failed. {:?}", command_name, why);
+        let store = {
+            let ctx_data = ctx.data.read().await;
+            ctx_data.get::<StoreKey>().expect("State store missing.").clone()
+        };
+        let record_result = store.lock().await.record_command_failure(command_name);
+        if let Err(err) = record_result {
+            eprintln!("Failed to count a command failure. {}", err);
+        }
+    }
+}
+
+struct Handler;
+
+#[async_trait]
+impl EventHandler for Handler {
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        println!("{} is connected!", ready.user.name);
+        if let Err(err) = slash::register_commands(&ctx).await {
+            eprintln!("Failed to register slash commands. {}", err);
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let interaction_result = match &interaction {
+            Interaction::Command(command) => slash::handle_command(&ctx, command).await,
+            Interaction::Autocomplete(autocomplete) => slash::handle_autocomplete(&ctx, autocomplete).await,
+            Interaction::Component(component) => navigate_with_component(&ctx, component).await.map_err(Into::into),
+            _ => Ok(()),
+        };
+        if let Err(err) = interaction_result {
+            eprintln!("Failed to respond to an interaction. {}", err);
+        }
+    }
+
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        // Ignore own reactions
+        if reaction.user_id == Some(ctx.cache.current_user().id) {
+            return;
+        }
+        // Record feedback.
+        if let Some(positive) = feedback_value(&reaction.emoji) {
+            record_feedback(&ctx, &reaction, positive).await;
+            return;
+        }
+        // Ignore reactions that aren't navigation.
+        let navigation = match Navigation::from_reaction(&reaction.emoji) {
+            Some(navigation) => navigation,
+            None => return,
+        };
+        let (response_map, store) = {
+            let ctx_data = ctx.data.read().await;
+            (
+                ctx_data
+                    .get::<RenderableResponseKey>()
+                    .expect("Could not fetch renderable response map.")
+                    .clone(),
+                ctx_data.get::<StoreKey>().expect("State store missing.").clone(),
+            )
+        };
+        let response_key = (reaction.channel_id, reaction.message_id);
+        // Ignore reactions to posts that don't have search cache. The response is copied out so the cache isn't locked
+        // while waiting on Discord.
+        let mut render_response = match response_map.lock().await.get(&response_key) {
+            Some(render_response) => render_response.clone(),
+            None => return,
+        };
+        let allowed = match reaction.user_id {
+            Some(user_id) => may_navigate(&ctx, &store, &render_response, reaction.guild_id, user_id, None).await,
+            None => false,
+        };
+        if !allowed {
+            if let Err(err) = reaction.delete(&ctx.http).await {
+                eprintln!("Failed to cull a reaction. {}", err);
+            }
+            return;
+        }
+        // Reactions leading nowhere, e.g. a member number on a page listing fewer members, are only removed.
+        if let Some(new_index) = render_response.target(render_response.index, navigation) {
+            // Get a message handle
+            let mut msg = match ctx.http.get_message(reaction.channel_id, reaction.message_id).await {
+                Ok(msg) => msg,
+                Err(err) => {
+                    eprintln!("Failed to get message handle for a reaction. {}", err);
+                    return;
+                }
+            };
+            // Render changes
+            match render_response.render(new_index, &ctx, &mut msg).await {
+                Ok(()) => (),
+                Err(err) => eprintln!("Failed to edit a message. {}", err),
+            };
+            if let Some(cached_response) = response_map.lock().await.get_mut(&response_key) {
+                cached_response.index = render_response.index;
+            }
+            let save_result = store
+                .lock()
+                .await
+                .save_response(reaction.channel_id, reaction.message_id, &render_response);
+            if let Err(err) = save_result {
+                eprintln!("Failed to save a response. {}", err);
+            }
+        }
+        // Delete navigation reactions.
+        match reaction.delete(ctx.http).await {
+            Ok(()) => (),
+            Err(err) => eprintln!("Failed to cull a reaction. {}", err),
+        };
+    }
+
+    async fn reaction_remove(&self, ctx: Context, reaction: Reaction) {
+        // Retract feedback.
+        if let (Some(positive), Some(user_id)) = (feedback_value(&reaction.emoji), reaction.user_id) {
+            let store = {
+                let ctx_data = ctx.data.read().await;
+                ctx_data.get::<StoreKey>().expect("State store missing.").clone()
+            };
+            let retract_result =
+                store
+                    .lock()
+                    .await
+                    .retract_feedback(reaction.channel_id, reaction.message_id, user_id, positive);
+            if let Err(err) = retract_result {
+                eprintln!("Failed to retract feedback. {}", err);
+            }
+        }
+    }
+}
+
+/// Returns whether a reaction is positive or negative feedback, or None if it isn't feedback.
+fn feedback_value(emoji: &ReactionType) -> Option<bool> {
+    match emoji {
+        ReactionType::Unicode(emoji) if *emoji == config().emoji.feedback_good => Some(true),
+        ReactionType::Unicode(emoji) if *emoji == config().emoji.feedback_bad => Some(false),
+        _ => None,
+    }
+}
+
+/// Records a feedback reaction on a search response, along with the query and the result being displayed.
+async fn record_feedback(ctx: &Context, reaction: &Reaction, positive: bool) {
+    let user_id = match reaction.user_id {
+        Some(user_id) => user_id,
+        None => return,
+    };
+    let ctx_data = ctx.data.read().await;
+    let store = ctx_data.get::<StoreKey>().expect("State store missing.");
+    let cached = {
+        let mut response_map = ctx_data
+            .get::<RenderableResponseKey>()
+            .expect("Could not fetch renderable response map.")
+            .lock()
+            .await;
+        response_map
+            .get(&(reaction.channel_id, reaction.message_id))
+            .map(|x| (x.query.clone(), x.messages[x.index].result.clone()))
+    };
+    let (query, guild_id, result) = match cached {
+        Some((query, result)) => (query, reaction.guild_id, result),
+        // Responses that are no longer cached can't tell which result was shown, but the search log still has the
+        // query, so the feedback is recorded without a result.
+        None => match store.lock().await.logged_search(reaction.channel_id, reaction.message_id) {
+            Ok(Some((query, guild_id))) => (query, guild_id, None),
+            // Ignore reactions to posts that aren't search responses
+            Ok(None) => return,
+            Err(err) => {
+                eprintln!("Failed to look up a logged search. {}", err);
+                return;
+            }
+        },
+    };
+    let feedback = Feedback {
+        channel_id: reaction.channel_id,
+        guild_id,
+        message_id: reaction.message_id,
+        positive,
+        query,
+        result,
+        user_id,
+    };
+    let record_result = store.lock().await.record_feedback(&feedback);
+    if let Err(err) = record_result {
+        eprintln!("Failed to record feedback. {}", err);
+    }
+}
+
+/// Returns whether a user may navigate a response.
+///
+/// The user who searched always may. Anyone else may if the guild allows open navigation, or if they can manage
+/// messages. `permissions` are looked up when not already known.
+async fn may_navigate(
+    ctx: &Context, store: &Mutex<Store>, render_response: &RenderableResponse, guild_id: Option<GuildId>,
+    user_id: UserId, permissions: Option<Permissions>,
+) -> bool {
+    match render_response.requester {
+        Some(requester) if requester != user_id => (),
+        _ => return true,
+    }
+    let guild_id = match guild_id {
+        Some(guild_id) => guild_id,
+        None => return false,
+    };
+    let open_result = store.lock().await.open_navigation(guild_id);
+    match open_result {
+        Ok(true) => return true,
+        Ok(false) => (),
+        Err(err) => eprintln!("Failed to read guild settings. {}", err),
+    }
+    let permissions = match permissions {
+        Some(permissions) => Some(permissions),
+        None => match guild_id.member(ctx, user_id).await {
+            Ok(member) => member.permissions(ctx).ok(),
+            Err(_) => None,
+        },
+    };
+    matches!(permissions, Some(permissions) if permissions.manage_messages())
+}
+
+/// Navigates a response with one of its buttons.
+async fn navigate_with_component(ctx: &Context, interaction: &ComponentInteraction) -> Result<(), serenity::Error> {
+    let navigation = match Navigation::from_component(&interaction.data) {
+        Some(navigation) => navigation,
+        None => return Ok(()),
+    };
+    let mut msg = (*interaction.message).clone();
+    let (response_map, store) = {
+        let ctx_data = ctx.data.read().await;
+        (
+            ctx_data
+                .get::<RenderableResponseKey>()
+                .expect("Could not fetch renderable response map.")
+                .clone(),
+            ctx_data.get::<StoreKey>().expect("State store missing.").clone(),
+        )
+    };
+    let mut response_map = response_map.lock().await;
+    let render_response = match response_map.get_mut(&(msg.channel_id, msg.id)) {
+        Some(render_response) => render_response,
+        None => {
+            // Expired, but the buttons weren't removed yet.
+            let expired_message = CreateInteractionResponseMessage::new()
+                .content("This response has expired. Search again to navigate it.")
+                .ephemeral(true);
+            interaction
+                .create_response(&ctx.http, CreateInteractionResponse::Message(expired_message))
+                .await?;
+            return Ok(());
+        }
+    };
+    let user_id = interaction.user.id;
+    let permissions = interaction.member.as_ref().and_then(|member| member.permissions);
+    if !may_navigate(ctx, &store, render_response, interaction.guild_id, user_id, permissions).await {
+        let denied_message = CreateInteractionResponseMessage::new()
+            .content("Only the person who searched can navigate these results.")
+            .ephemeral(true);
+        interaction
+            .create_response(&ctx.http, CreateInteractionResponse::Message(denied_message))
+            .await?;
+        return Ok(());
+    }
+    interaction
+        .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+        .await?;
+    if let Some(new_index) = render_response.target(render_response.index, navigation) {
+        render_response.render(new_index, ctx, &mut msg).await?;
+        let save_result = store.lock().await.save_response(msg.channel_id, msg.id, render_response);
+        if let Err(err) = save_result {
+            eprintln!("Failed to save a response. {}", err);
+        }
+    }
+    Ok(())
+}
+
+/// Connects to Discord and answers commands until the client stops. Fails if startup fails.
+pub async fn run_bot(token: &str) -> Result<(), String> {
+    let http = Http::new(token);
+
+    // Set up global owners
+    let (owners, bot_id, application_id) = match http.get_current_application_info().await {
+        Ok(info) => {
+            let mut owners = HashSet::new();
+            if let Some(team) = info.team {
+                owners.insert(team.owner_user_id);
+            } else if let Some(owner) = info.owner {
+                owners.insert(owner.id);
+            }
+            match http.get_current_user().await {
+                Ok(bot_id) => (owners, bot_id.id, info.id),
+                Err(why) => return Err(format!("Could not access the bot id: {:?}", why)),
+            }
+        }
+        Err(why) => return Err(format!("Could not access application info: {:?}", why)),
+    };
+
+    // Build command framework
+    let framework = StandardFramework::new()
+        .before(before)
+        .after(after)
+        .group(&CMDASK_GROUP)
+        .group(&CMDUTIL_GROUP);
+    framework.configure(
+        Configuration::new()
+            .with_whitespace(true)
+            .on_mention(Some(bot_id))
+            // Static prefixes always match, so leave them empty and let guilds choose their own.
+            .prefix("")
+            .dynamic_prefix(dynamic_prefix)
+            .delimiters(config().bot.delimiters.clone())
+            .owners(owners.clone()),
+    );
+
+    // Build search backend
+    let search_backend = match build_search_backend() {
+        Ok(backend) => backend,
+        Err(why) => return Err(format!("Could not load search data: {}", why)),
+    };
+
+    let search_backend = Arc::new(RwLock::new(search_backend));
+
+    // Open state store
+    let store = match Store::open(&config().store.path) {
+        Ok(store) => store,
+        Err(why) => {
+            return Err(format!(
+                "Could not open state store {}: {}",
+                config().store.path.display(),
+                why
+            ))
+        }
+    };
+
+    let store = Arc::new(Mutex::new(store));
+    let response_map = Arc::new(Mutex::new(build_response_map()));
+
+    // Start client
+    // Prefix commands are read from message content, which is a privileged intent.
+    let intents = GatewayIntents::non_privileged() | GatewayIntents::MESSAGE_CONTENT;
+    let mut client = Client::builder(token, intents)
+        .application_id(application_id)
+        .event_handler(Handler)
+        .framework(framework)
+        .type_map_insert::<SearchDataKey>(search_backend.clone())
+        .type_map_insert::<RenderableResponseKey>(response_map.clone())
+        .type_map_insert::<StoreKey>(store.clone())
+        .type_map_insert::<StartTimeKey>(Instant::now())
+        .await
+        .map_err(|why| format!("Could not create the client: {:?}", why))?;
+    client
+        .data
+        .write()
+        .await
+        .insert::<ShardManagerContainer>(client.shard_manager.clone());
+    watch_search_backend(search_backend, client.http.clone(), owners).await;
+    restore_responses(&response_map, &store, &client.http).await;
+    expire_responses(response_map, store, client.http.clone());
+    client
+        .start_autosharded()
+        .await
+        .map_err(|why| format!("Client error: {:?}", why))
+}
@@ -0,0 +1,239 @@
+//! This file implements a size bounded cache with least recently used eviction and a fixed time to live.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+/// A single cached value.
+#[derive(Clone, Debug)]
+struct CacheEntry<V> {
+    /// When the entry stops being returned.
+    expires_at: Instant,
+    /// Recency tick of the last access.
+    last_used: u64,
+    /// The cached value.
+    value: V,
+}
+
+/// Cache holding at most `capacity` entries, each for at most `ttl` after insertion.
+///
+/// Expired and evicted entries are handed back to the caller so any external state tied to them can be cleaned up.
+#[derive(Clone, Debug)]
+pub struct LruCache<K, V> {
+    /// Maximum number of entries.
+    capacity: usize,
+    /// Cached entries by key.
+    entries: HashMap<K, CacheEntry<V>>,
+    /// Keys ordered from least to most recently used.
+    recency: BTreeMap<u64, K>,
+    /// Next recency tick to hand out.
+    tick: u64,
+    /// How long an entry lives after insertion.
+    ttl: Duration,
+}
+
+impl<K: Clone + Eq + Hash, V> LruCache<K, V> {
+    /// Creates an empty cache. A capacity of 0 is treated as 1.
+    pub fn new(capacity: usize, ttl: Duration) -> LruCache<K, V> {
+        LruCache {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            ttl,
+        }
+    }
+
+    /// Returns a live entry, marking it as recently used.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    /// Returns a live entry mutably, marking it as recently used.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let tick = self.next_tick();
+        let entry = self.entries.get_mut(key)?;
+        if entry.expires_at <= Instant::now() {
+            return None;
+        }
+        self.recency.remove(&entry.last_used);
+        self.recency.insert(tick, key.clone());
+        entry.last_used = tick;
+        Some(&mut entry.value)
+    }
+
+    /// Inserts or replaces an entry, returning any entries evicted to stay within capacity.
+    pub fn insert(&mut self, key: K, value: V) -> Vec<(K, V)> {
+        self.insert_aged(key, value, Duration::from_secs(0))
+    }
+
+    /// Inserts or replaces an entry that was created `age` ago, so it expires that much sooner.
+    pub fn insert_aged(&mut self, key: K, value: V, age: Duration) -> Vec<(K, V)> {
+        let tick = self.next_tick();
+        let entry = CacheEntry {
+            expires_at: Instant::now() + self.ttl.checked_sub(age).unwrap_or_default(),
+            last_used: tick,
+            value,
+        };
+        if let Some(old_entry) = self.entries.insert(key.clone(), entry) {
+            self.recency.remove(&old_entry.last_used);
+        }
+        self.recency.insert(tick, key);
+        let mut evicted = Vec::<(K, V)>::new();
+        while self.entries.len() > self.capacity {
+            let oldest_tick = match self.recency.keys().next() {
+                Some(oldest_tick) => *oldest_tick,
+                None => break,
+            };
+            if let Some(oldest_key) = self.recency.remove(&oldest_tick) {
+                if let Some(oldest_entry) = self.entries.remove(&oldest_key) {
+                    evicted.push((oldest_key, oldest_entry.value));
+                }
+            }
+        }
+        evicted
+    }
+
+    /// Returns the number of entries, including expired entries not yet removed.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes and returns every expired entry.
+    pub fn remove_expired(&mut self) -> Vec<(K, V)> {
+        let now = Instant::now();
+        let expired_keys = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.expires_at <= now)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<K>>();
+        let mut expired = Vec::<(K, V)>::new();
+        for key in expired_keys {
+            if let Some(entry) = self.entries.remove(&key) {
+                self.recency.remove(&entry.last_used);
+                expired.push((key, entry.value));
+            }
+        }
+        expired
+    }
+
+    /// Hands out a new, strictly increasing recency tick.
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn evicts_least_recently_inserted_first() {
+        let mut cache = LruCache::new(2, TTL);
+        assert!(cache.insert(1, "titan").is_empty());
+        assert!(cache.insert(2, "garuda").is_empty());
+        assert_eq!(cache.insert(3, "ifrit"), vec![(1, "titan")]);
+        assert_eq!(cache.insert(4, "leviathan"), vec![(2, "garuda")]);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn reading_an_entry_keeps_it_from_eviction() {
+        let mut cache = LruCache::new(2, TTL);
+        cache.insert(1, "titan");
+        cache.insert(2, "garuda");
+        assert_eq!(cache.get(&1), Some(&"titan"));
+        assert_eq!(cache.insert(3, "ifrit"), vec![(2, "garuda")]);
+        assert_eq!(cache.get_mut(&1).map(|x| *x), Some("titan"));
+        assert_eq!(cache.insert(4, "leviathan"), vec![(3, "ifrit")]);
+    }
+
+    #[test]
+    fn replacing_an_entry_does_not_evict() {
+        let mut cache = LruCache::new(2, TTL);
+        cache.insert(1, "titan");
+        cache.insert(2, "garuda");
+        assert!(cache.insert(1, "titan ex").is_empty());
+        assert_eq!(cache.get(&1), Some(&"titan ex"));
+        assert_eq!(cache.insert(3, "ifrit"), vec![(2, "garuda")]);
+    }
+
+    #[test]
+    fn zero_capacity_holds_one_entry() {
+        let mut cache = LruCache::new(0, TTL);
+        assert!(cache.insert(1, "titan").is_empty());
+        assert_eq!(cache.insert(2, "garuda"), vec![(1, "titan")]);
+        assert_eq!(cache.get(&2), Some(&"garuda"));
+    }
+
+    #[test]
+    fn entries_expire_after_ttl() {
+        let mut cache = LruCache::new(2, Duration::from_millis(20));
+        cache.insert(1, "titan");
+        assert_eq!(cache.get(&1), Some(&"titan"));
+        sleep(Duration::from_millis(30));
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get_mut(&1), None);
+        // Expired entries stay counted until they are removed.
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn aged_entries_expire_sooner() {
+        let mut cache = LruCache::new(3, TTL);
+        cache.insert_aged(1, "titan", Duration::from_secs(30));
+        cache.insert_aged(2, "garuda", TTL);
+        cache.insert_aged(3, "ifrit", TTL * 2);
+        assert_eq!(cache.get(&1), Some(&"titan"));
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&3), None);
+    }
+
+    #[test]
+    fn aged_entries_are_evicted_like_any_other() {
+        let mut cache = LruCache::new(1, TTL);
+        cache.insert(1, "titan");
+        assert_eq!(
+            cache.insert_aged(2, "garuda", Duration::from_secs(30)),
+            vec![(1, "titan")]
+        );
+    }
+
+    #[test]
+    fn remove_expired_returns_only_expired_entries() {
+        let mut cache = LruCache::new(3, TTL);
+        cache.insert_aged(1, "titan", TTL);
+        cache.insert(2, "garuda");
+        cache.insert_aged(3, "ifrit", TTL);
+        let mut expired = cache.remove_expired();
+        expired.sort();
+        assert_eq!(expired, vec![(1, "titan"), (3, "ifrit")]);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.remove_expired().is_empty());
+        assert_eq!(cache.get(&2), Some(&"garuda"));
+    }
+
+    #[test]
+    fn removed_entries_free_their_place() {
+        let mut cache = LruCache::new(2, TTL);
+        cache.insert_aged(1, "titan", TTL);
+        cache.insert(2, "garuda");
+        cache.remove_expired();
+        assert!(cache.insert(3, "ifrit").is_empty());
+        assert_eq!(cache.insert(4, "leviathan"), vec![(2, "garuda")]);
+        assert!(!cache.is_empty());
+    }
+}
@@ -1 +1,493 @@
+use serenity::prelude::*;
+use serenity::{
+    builder::{CreateAttachment, CreateMessage},
+    framework::standard::{
+        macros::{command, group},
+        Args, CommandResult,
+    },
+    model::{
+        channel::Message,
+        id::{ChannelId, GuildId},
+    },
+    utils::parse_channel_mention,
+};
 
+use crate::commands::util::guild_prefix;
+use crate::config::{config, valid_prefix};
+use crate::consts::*;
+use crate::search::backend::SearchDataKey;
+use crate::store::{report::Report, StoreKey};
+use crate::render::{RenderableEmbed, RenderableMessage, RenderableResponse};
+
+/// Container for commands managing the channels paxbot answers searches in. Registered as a sub group of
+/// [`crate::commands::util::CmdUtil`], as `?!pax chan`.
+#[group]
+#[prefix = "chan"]
+#[only_in(guilds)]
+#[required_permissions("ADMINISTRATOR")]
+#[default_command(chan_list)]
+#[commands(chan_add, chan_list, chan_remove)]
+pub struct CmdChan;
+
+/// Allows searches in the mentioned channel, or the current channel if none is mentioned.
+#[command("add")]
+async fn chan_add(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (guild_id, channel_id) = match (msg.guild_id, channel_arg(ctx, msg, &args).await) {
+        (Some(guild_id), Some(channel_id)) => (guild_id, channel_id),
+        _ => {
+            let prefix = guild_prefix(ctx, msg.guild_id).await;
+            msg.reply(
+                &ctx.http,
+                format!(
+                    "Mention a channel in this server, like `{}!pax chan add #tips`.",
+                    prefix
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let store = {
+        let ctx_data = ctx.data.read().await;
+        ctx_data.get::<StoreKey>().expect("State store missing.").clone()
+    };
+    let allow_result = store.lock().await.allow_channel(guild_id, channel_id);
+    let reply = match allow_result {
+        Ok(true) => format!("paxbot now answers searches in {}.", channel_id.mention()),
+        Ok(false) => format!("paxbot already answers searches in {}.", channel_id.mention()),
+        Err(err) => {
+            eprintln!("Failed to save the channel allowlist. {}", err);
+            String::from("Failed to save the channel allowlist. Check the bot logs for details.")
+        }
+    };
+    msg.reply(&ctx.http, reply).await?;
+    Ok(())
+}
+
+/// Lists the channels searches are allowed in.
+#[command("list")]
+async fn chan_list(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+    let store = {
+        let ctx_data = ctx.data.read().await;
+        ctx_data.get::<StoreKey>().expect("State store missing.").clone()
+    };
+    let list_result = store.lock().await.allowed_channels(guild_id);
+    let reply = match list_result {
+        Ok(channels) if channels.is_empty() => format!(
+            "paxbot answers searches in every channel. Use `{}!pax chan add #channel` to limit it to some channels.",
+            guild_prefix(ctx, Some(guild_id)).await
+        ),
+        Ok(channels) => format!(
+            "paxbot only answers searches in: {}",
+            channels
+                .iter()
+                .map(|x| x.mention().to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        Err(err) => {
+            eprintln!("Failed to read the channel allowlist. {}", err);
+            String::from("Failed to read the channel allowlist. Check the bot logs for details.")
+        }
+    };
+    msg.reply(&ctx.http, reply).await?;
+    Ok(())
+}
+
+/// Stops allowing searches in the mentioned channel, or the current channel if none is mentioned.
+#[command("remove")]
+async fn chan_remove(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (guild_id, channel_id) = match (msg.guild_id, channel_arg(ctx, msg, &args).await) {
+        (Some(guild_id), Some(channel_id)) => (guild_id, channel_id),
+        _ => {
+            let prefix = guild_prefix(ctx, msg.guild_id).await;
+            msg.reply(
+                &ctx.http,
+                format!(
+                    "Mention a channel in this server, like `{}!pax chan remove #tips`.",
+                    prefix
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let store = {
+        let ctx_data = ctx.data.read().await;
+        ctx_data.get::<StoreKey>().expect("State store missing.").clone()
+    };
+    let (disallow_result, remaining) = {
+        let store = store.lock().await;
+        (
+            store.disallow_channel(guild_id, channel_id),
+            store.allowed_channels(guild_id).map(|x| x.len()).unwrap_or(0),
+        )
+    };
+    let reply = match disallow_result {
+        Ok(true) if remaining == 0 => format!(
+            "Removed {}. No channels are left on the list, so paxbot answers searches everywhere again.",
+            channel_id.mention()
+        ),
+        Ok(true) => format!("paxbot no longer answers searches in {}.", channel_id.mention()),
+        Ok(false) => format!("{} isn't on the list.", channel_id.mention()),
+        Err(err) => {
+            eprintln!("Failed to save the channel allowlist. {}", err);
+            String::from("Failed to save the channel allowlist. Check the bot logs for details.")
+        }
+    };
+    msg.reply(&ctx.http, reply).await?;
+    Ok(())
+}
+
+/// Returns the channel mentioned as the first argument, or the current channel if there are no arguments.
+/// Returns None if the channel isn't part of the message's guild.
+async fn channel_arg(ctx: &Context, msg: &Message, args: &Args) -> Option<ChannelId> {
+    let channel_id = match args.current() {
+        Some(arg) => parse_channel_mention(arg).or_else(|| arg.parse::<ChannelId>().ok())?,
+        None => return Some(msg.channel_id),
+    };
+    let in_guild = msg.guild(&ctx.cache)?.channels.contains_key(&channel_id);
+    if in_guild {
+        Some(channel_id)
+    } else {
+        None
+    }
+}
+
+/// Reloads search data from its backend, keeping the previous data if the new data fails to load.
+#[command]
+#[owners_only]
+async fn reload(ctx: &Context, msg: &Message) -> CommandResult {
+    let search_backend = {
+        let ctx_data = ctx.data.read().await;
+        ctx_data.get::<SearchDataKey>().expect("Search data missing.").clone()
+    };
+    // Content is read and indexed under the read lock, so searches keep being answered until it is swapped in.
+    let prepare_result = search_backend.read().await.prepare();
+    match prepare_result {
+        Ok(prepared) => {
+            let reply = format!(
+                "Reloaded {} categories and {} search results.",
+                prepared.data.categories.len(),
+                prepared.data.search_results.len()
+            );
+            search_backend.write().await.replace(prepared);
+            msg.reply(&ctx.http, reply).await?;
+        }
+        Err(why) => {
+            msg.reply(
+                &ctx.http,
+                format!("Reload failed. Keeping previously loaded data.\n```text\n{}\n```", why),
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints the feedback and content gap report for this server. `?!pax report csv` uploads the report as a CSV file
+/// instead.
+#[command]
+#[only_in(guilds)]
+#[required_permissions("ADMINISTRATOR")]
+async fn report(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    match msg.guild_id {
+        Some(guild_id) => send_report(ctx, msg, args, Some(guild_id)).await,
+        None => Ok(()),
+    }
+}
+
+/// Prints the feedback and content gap report for every server. `?!pax reportall csv` uploads it as a CSV file.
+#[command("reportall")]
+#[owners_only]
+async fn report_all(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_report(ctx, msg, args, None).await
+}
+
+/// Posts the report for `guild_id`, or for every server if None, as navigable pages or as a CSV file.
+async fn send_report(ctx: &Context, msg: &Message, mut args: Args, guild_id: Option<GuildId>) -> CommandResult {
+    let as_csv = match args.single::<String>() {
+        Ok(format) if format.eq_ignore_ascii_case("csv") => true,
+        Ok(format) => {
+            let prefix = guild_prefix(ctx, msg.guild_id).await;
+            msg.reply(
+                &ctx.http,
+                format!(
+                    "Unknown report format `{}`. Try `{}!pax {} csv`.",
+                    format,
+                    prefix,
+                    if guild_id.is_some() { "report" } else { "reportall" }
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+        Err(_) => false,
+    };
+    let store = {
+        let ctx_data = ctx.data.read().await;
+        ctx_data.get::<StoreKey>().expect("State store missing.").clone()
+    };
+    let row_limit = if as_csv { REPORT_CSV_ROW_LIMIT } else { REPORT_ROW_LIMIT };
+    let report_result = store.lock().await.report(guild_id, row_limit);
+    let report = match report_result {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("Failed to build a report. {}", err);
+            msg.reply(&ctx.http, "Failed to build the report. Check the bot logs for details.")
+                .await?;
+            return Ok(());
+        }
+    };
+    let scope = match guild_id {
+        Some(_) => "this server",
+        None => "all servers",
+    };
+    if as_csv {
+        msg.channel_id
+            .send_files(
+                &ctx.http,
+                vec![CreateAttachment::bytes(report_csv(&report), "paxbot-report.csv")],
+                CreateMessage::new().content(format!("Content report for {}.", scope)),
+            )
+            .await?;
+        return Ok(());
+    }
+    // Post result container --- this will get edited with the first page.
+    let reply_msg = msg.channel_id.say(&ctx.http, "Building report...").await?;
+    let mut editable_msg = ctx
+        .http
+        .get_message(reply_msg.channel_id, reply_msg.id)
+        .await?;
+    report_response(&report, scope)
+        .attach(ctx, &mut editable_msg, msg.author.id)
+        .await?;
+    Ok(())
+}
+
+/// Shows or sets who may navigate search results in this server. `open` lets anyone navigate, `requester` limits
+/// navigation to the user who searched. Members who can manage messages may always navigate.
+#[command]
+#[aliases("nav")]
+#[only_in(guilds)]
+#[required_permissions("ADMINISTRATOR")]
+async fn navigation(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+    let store = {
+        let ctx_data = ctx.data.read().await;
+        ctx_data.get::<StoreKey>().expect("State store missing.").clone()
+    };
+    let open = match args.single::<String>().map(|x| x.to_lowercase()).as_deref() {
+        Ok("open") => true,
+        Ok("requester") => false,
+        Ok(other) => {
+            msg.reply(
+                &ctx.http,
+                format!("Unknown navigation mode `{}`. Use `open` or `requester`.", other),
+            )
+            .await?;
+            return Ok(());
+        }
+        Err(_) => {
+            let open_result = store.lock().await.open_navigation(guild_id);
+            let reply = match open_result {
+                Ok(true) => "Anyone can navigate search results in this server.",
+                Ok(false) => "Only the user who searched, and moderators, can navigate search results in this server.",
+                Err(err) => {
+                    eprintln!("Failed to read guild settings. {}", err);
+                    "Failed to read this server's settings. Check the bot logs for details."
+                }
+            };
+            msg.reply(&ctx.http, reply).await?;
+            return Ok(());
+        }
+    };
+    let set_result = store.lock().await.set_open_navigation(guild_id, open);
+    let reply = match set_result {
+        Ok(()) if open => "Anyone can now navigate search results in this server.",
+        Ok(()) => "Only the user who searched, and moderators, can now navigate search results in this server.",
+        Err(err) => {
+            eprintln!("Failed to save guild settings. {}", err);
+            "Failed to save this server's settings. Check the bot logs for details."
+        }
+    };
+    msg.reply(&ctx.http, reply).await?;
+    Ok(())
+}
+
+/// Splits each section of a report into pages of [`REPORT_PAGE_ROWS`] rows.
+fn report_response(report: &Report, scope: &str) -> RenderableResponse {
+    let sections = vec![
+        (
+            "Most Disliked Results",
+            report
+                .disliked
+                .iter()
+                .map(|x| {
+                    format!(
+                        "**{}** — {} {} / {} {}",
+                        x.result,
+                        config().emoji.feedback_bad,
+                        x.bad,
+                        config().emoji.feedback_good,
+                        x.good
+                    )
+                })
+                .collect::<Vec<String>>(),
+        ),
+        (
+            "Queries With No Results",
+            report
+                .missed
+                .iter()
+                .map(|x| format!("`{}` — {} search(es)", x.query, x.count))
+                .collect::<Vec<String>>(),
+        ),
+        (
+            "Ignored Best Guesses",
+            report
+                .ignored_guesses
+                .iter()
+                .map(|x| format!("`{}` → **{}** — {} time(s)", x.query, x.guess, x.count))
+                .collect::<Vec<String>>(),
+        ),
+    ];
+    let mut pages = Vec::<(String, String)>::new();
+    for (title, rows) in sections {
+        if rows.is_empty() {
+            pages.push((title.to_string(), String::from("Nothing to report.")));
+            continue;
+        }
+        for page_rows in rows.chunks(REPORT_PAGE_ROWS) {
+            pages.push((title.to_string(), page_rows.join("\n")));
+        }
+    }
+    let page_count = pages.len();
+    RenderableResponse {
+        buttons: false,
+        index: 0,
+        messages: pages
+            .into_iter()
+            .enumerate()
+            .map(|(i, (title, description))| RenderableMessage {
+                category_index: None,
+                content: String::new(),
+                embed: Some(RenderableEmbed {
+                    description: Some(description),
+                    fields: None,
+                    footer: Some(format!("Report for {} | Page {} of {}", scope, i + 1, page_count)),
+                    title: format!("Content Report: {}", title),
+                }),
+                member_only: false,
+                members: Vec::new(),
+                result: None,
+            })
+            .collect(),
+        query: String::new(),
+        requester: None,
+        text_only: false,
+    }
+}
+
+/// Formats a report as CSV, one row per entry with a column naming its section.
+fn report_csv(report: &Report) -> String {
+    let mut csv = String::from("section,query,result,count,good,bad\n");
+    for x in &report.disliked {
+        csv_row(
+            &mut csv,
+            &["disliked", "", &x.result, "", &x.good.to_string(), &x.bad.to_string()],
+        );
+    }
+    for x in &report.missed {
+        csv_row(&mut csv, &["missed", &x.query, "", &x.count.to_string(), "", ""]);
+    }
+    for x in &report.ignored_guesses {
+        csv_row(
+            &mut csv,
+            &["ignored_guess", &x.query, &x.guess, &x.count.to_string(), "", ""],
+        );
+    }
+    csv
+}
+
+/// Appends a CSV row, quoting fields that contain separators, quotes or line breaks.
+fn csv_row(csv: &mut String, fields: &[&str]) {
+    let fields = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect::<Vec<String>>();
+    csv.push_str(&fields.join(","));
+    csv.push('\n');
+}
+
+/// Shows or sets the command prefix for this server. `reset` goes back to the default prefix. Mentioning paxbot works
+/// in place of the prefix, in case the prefix is forgotten.
+#[command]
+#[only_in(guilds)]
+#[required_permissions("ADMINISTRATOR")]
+async fn prefix(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+    let new_prefix = match args.rest().trim() {
+        "" => {
+            let prefix = guild_prefix(ctx, Some(guild_id)).await;
+            msg.reply(
+                &ctx.http,
+                format!("Commands in this server start with `{}`, like `{}pax`.", prefix, prefix),
+            )
+            .await?;
+            return Ok(());
+        }
+        x if x.eq_ignore_ascii_case("reset") => None,
+        x if !valid_prefix(x) => {
+            msg.reply(
+                &ctx.http,
+                format!(
+                    "Prefixes can be at most {} characters, without spaces or backticks.",
+                    PREFIX_MAX_LEN
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+        x => Some(x),
+    };
+    let store = {
+        let ctx_data = ctx.data.read().await;
+        ctx_data.get::<StoreKey>().expect("State store missing.").clone()
+    };
+    let set_result = match new_prefix {
+        Some(new_prefix) => store.lock().await.set_prefix(guild_id, new_prefix),
+        None => store.lock().await.clear_prefix(guild_id),
+    };
+    let reply = match set_result {
+        Ok(()) => {
+            let prefix = new_prefix.unwrap_or(&config().bot.prefix);
+            format!(
+                "Commands in this server now start with `{}`, like `{}pax`.",
+                prefix, prefix
+            )
+        }
+        Err(err) => {
+            eprintln!("Failed to save guild settings. {}", err);
+            String::from("Failed to save this server's settings. Check the bot logs for details.")
+        }
+    };
+    msg.reply(&ctx.http, reply).await?;
+    Ok(())
+}
@@ -1,73 +1,122 @@
 use serenity::prelude::*;
 use serenity::{
     framework::standard::{
-        macros::{command, group},
-        Args, CommandResult,
+        macros::{check, command, group},
+        Args, CommandOptions, CommandResult, Reason,
+    },
+    model::{
+        channel::{Message, ReactionType},
+        id::{ChannelId, GuildId, UserId},
     },
-    model::channel::{Message, ReactionType},
 };
 
 use crate::commands::util::print_help;
-use crate::consts::*;
+use crate::config::config;
 use crate::search::{backend::SearchDataKey, search, RenderType};
-use crate::RenderableResponseKey;
+use crate::store::{search_log::SearchLogEntry, StoreKey};
 
 /// Container for the primary query command.
 #[group]
+#[checks(AllowedChannel)]
 #[commands(ask)]
 pub struct CmdAsk;
 
+// Silently ignores searches outside the guild's channel allowlist. Checks can't carry doc comments.
+#[check]
+#[name = "AllowedChannel"]
+async fn allowed_channel_check(
+    ctx: &Context, msg: &Message, _: &mut Args, _: &CommandOptions,
+) -> Result<(), Reason> {
+    if channel_allowed(ctx, msg.guild_id, msg.channel_id).await {
+        Ok(())
+    } else {
+        Err(Reason::Log(format!("Ignoring a search in {}, which is not allowed.", msg.channel_id)))
+    }
+}
+
+/// Returns whether searches are answered in a channel. DMs are always allowed.
+pub async fn channel_allowed(ctx: &Context, guild_id: Option<GuildId>, channel_id: ChannelId) -> bool {
+    let guild_id = match guild_id {
+        Some(guild_id) => guild_id,
+        None => return true,
+    };
+    let store = {
+        let ctx_data = ctx.data.read().await;
+        ctx_data.get::<StoreKey>().expect("State store missing.").clone()
+    };
+    let allowed_result = store.lock().await.channel_allowed(guild_id, channel_id);
+    match allowed_result {
+        Ok(allowed) => allowed,
+        Err(err) => {
+            eprintln!("Failed to read the channel allowlist. {}", err);
+            true
+        }
+    }
+}
+
 #[command("pax")] // This results in ?pax being read as the command, with the rest being args
 async fn ask(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let search_query = args.rest();
     if search_query.is_empty() {
-        return print_help(&ctx, &msg).await
+        return print_help(ctx, msg).await
     }
     // Post result container --- this will get edited when response arrives.
     let reply_msg = msg.channel_id.say(&ctx.http, "Searching...").await?;
-    // Do a search
-    let search_response = {
-        let ctx_data = ctx.data.read().await;
-        let search_data_ref = ctx_data.get::<SearchDataKey>().expect("Search data missing.");
-        search(&search_query, search_data_ref).await
-    };
     // Get a mutable message handle for rendering
-    let mut editable_msg = ctx
+    let editable_msg = ctx
         .http
-        .get_message(*reply_msg.channel_id.as_u64(), *reply_msg.id.as_u64())
+        .get_message(reply_msg.channel_id, reply_msg.id)
         .await?;
+    answer(ctx, search_query, editable_msg, msg.author.id, msg.guild_id).await
+}
+
+/// Searches for `query` and renders the response into `reply_msg`, a placeholder message posted by paxbot.
+///
+/// Shared by `?pax` and `/pax`, so both paths build responses the same way.
+pub async fn answer(
+    ctx: &Context, query: &str, mut reply_msg: Message, user_id: UserId, guild_id: Option<GuildId>,
+) -> CommandResult {
+    // Do a search
+    let search_response = {
+        let search_backend = {
+            let ctx_data = ctx.data.read().await;
+            ctx_data.get::<SearchDataKey>().expect("Search data missing.").clone()
+        };
+        let search_backend = search_backend.read().await;
+        search(query, search_backend.as_ref()).await
+    };
     // Render result
-    let mut render_response = search_response.get_renderable_response();
-    render_response.render(0, &ctx, &mut editable_msg).await?;
-    // Set up navigation reactions
-    if render_response.messages.len() > 1 {
-        reply_msg
-            .react(&ctx.http, ReactionType::Unicode(String::from(REACT_RESULTS_BACKWARD)))
-            .await?;
-        reply_msg
-            .react(&ctx.http, ReactionType::Unicode(String::from(REACT_RESULTS_FORWARD)))
-            .await?;
-    }
+    search_response
+        .get_renderable_response()
+        .attach(ctx, &mut reply_msg, user_id)
+        .await?;
     // Set up feedback reactions
     match search_response.render_type {
         RenderType::Category | RenderType::Result => {
             reply_msg
-                .react(&ctx.http, ReactionType::Unicode(String::from(REACT_FEEDBACK_GOOD)))
+                .react(&ctx.http, ReactionType::Unicode(config().emoji.feedback_good.clone()))
                 .await?;
             reply_msg
-                .react(&ctx.http, ReactionType::Unicode(String::from(REACT_FEEDBACK_BAD)))
+                .react(&ctx.http, ReactionType::Unicode(config().emoji.feedback_bad.clone()))
                 .await?;
         }
         _ => (),
     }
-    // Write context data
-    let mut ctx_data = ctx.data.write().await;
-    let resp_map = ctx_data
-        .get_mut::<RenderableResponseKey>()
-        .expect("Failed to get render response map.");
-    resp_map
-        .lock()
-        .await
-        .insert((reply_msg.channel_id, reply_msg.id), render_response);
+    // Log the search for maintainer reports
+    let store = {
+        let ctx_data = ctx.data.read().await;
+        ctx_data.get::<StoreKey>().expect("State store missing.").clone()
+    };
+    let log_result = store.lock().await.log_search(&SearchLogEntry {
+        channel_id: reply_msg.channel_id,
+        guild_id,
+        message_id: reply_msg.id,
+        query: search_response.query.clone(),
+        render_type: search_response.render_type.clone(),
+        user_id,
+    });
+    if let Err(err) = log_result {
+        eprintln!("Failed to log a search. {}", err);
+    }
     Ok(())
 }
@@ -1,2 +1,4 @@
+pub mod admin;
 pub mod ask;
+pub mod slash;
 pub mod util;
@@ -0,0 +1,148 @@
+//! This file implements `/pax`, the application (slash) command version of `?pax`.
+//! Responses are built by [`answer`], the same pipeline the prefix command uses. The query autocompletes from entry
+//! names, shortnames and category names.
+
+use serenity::prelude::*;
+use serenity::{
+    builder::{
+        CreateAutocompleteResponse, CreateCommand, CreateCommandOption, CreateInteractionResponse,
+        CreateInteractionResponseMessage,
+    },
+    framework::standard::CommandResult,
+    model::application::{Command, CommandDataOption, CommandInteraction, CommandOptionType},
+};
+
+use crate::commands::ask::{answer, channel_allowed};
+use crate::commands::util::guild_prefix;
+use crate::search::backend::{SearchBackendData, SearchDataKey};
+use crate::store::StoreKey;
+
+/// Most suggestions Discord accepts for a single autocomplete request.
+const AUTOCOMPLETE_LIMIT: usize = 25;
+/// Longest suggestion Discord accepts, in characters.
+const AUTOCOMPLETE_NAME_LIMIT: usize = 100;
+
+/// Registers `/pax` as a global application command, replacing any previous definition.
+pub async fn register_commands(ctx: &Context) -> serenity::Result<()> {
+    let query = CreateCommandOption::new(CommandOptionType::String, "query", "What to search for.")
+        .required(true)
+        .set_autocomplete(true);
+    let command = CreateCommand::new("pax")
+        .description("Searches the paxbot tip database.")
+        .add_option(query);
+    Command::create_global_command(&ctx.http, command).await?;
+    Ok(())
+}
+
+/// Responds to a `/pax` interaction. Other commands are ignored.
+pub async fn handle_command(ctx: &Context, interaction: &CommandInteraction) -> CommandResult {
+    if interaction.data.name != "pax" {
+        return Ok(());
+    }
+    let query = query_option(&interaction.data.options).unwrap_or_default();
+    if query.trim().is_empty() {
+        let prefix = guild_prefix(ctx, interaction.guild_id).await;
+        let help_message = CreateInteractionResponseMessage::new()
+            .content(format!(
+                "Enter something to search for. `{}pax` prints the full help.",
+                prefix
+            ))
+            .ephemeral(true);
+        interaction
+            .create_response(&ctx.http, CreateInteractionResponse::Message(help_message))
+            .await?;
+        return Ok(());
+    }
+    if !channel_allowed(ctx, interaction.guild_id, interaction.channel_id).await {
+        let denied_message = CreateInteractionResponseMessage::new()
+            .content("paxbot doesn't answer searches in this channel.")
+            .ephemeral(true);
+        interaction
+            .create_response(&ctx.http, CreateInteractionResponse::Message(denied_message))
+            .await?;
+        return Ok(());
+    }
+    let user_id = interaction.user.id;
+    // Slash commands don't pass through the framework hooks, so count the run here.
+    let store = {
+        let ctx_data = ctx.data.read().await;
+        ctx_data.get::<StoreKey>().expect("State store missing.").clone()
+    };
+    let record_result = store.lock().await.record_command("pax", user_id);
+    if let Err(err) = record_result {
+        eprintln!("Failed to count a command. {}", err);
+    }
+    // Post result container --- this will get edited when response arrives.
+    let searching_message = CreateInteractionResponseMessage::new().content("Searching...");
+    interaction
+        .create_response(&ctx.http, CreateInteractionResponse::Message(searching_message))
+        .await?;
+    let reply_msg = interaction.get_response(&ctx.http).await?;
+    answer(ctx, &query, reply_msg, user_id, interaction.guild_id).await
+}
+
+/// Returns the value of the `query` option.
+fn query_option(options: &[CommandDataOption]) -> Option<String> {
+    options
+        .iter()
+        .find(|option| option.name == "query")
+        .and_then(|option| option.value.as_str())
+        .map(String::from)
+}
+
+/// Suggests queries for the `/pax` query option as it is typed. Other commands are ignored.
+pub async fn handle_autocomplete(ctx: &Context, interaction: &CommandInteraction) -> CommandResult {
+    let partial = match interaction.data.autocomplete() {
+        Some(option) if interaction.data.name == "pax" && option.name == "query" => option.value,
+        _ => return Ok(()),
+    };
+    let suggestions = {
+        let search_backend = {
+            let ctx_data = ctx.data.read().await;
+            ctx_data.get::<SearchDataKey>().expect("Search data missing.").clone()
+        };
+        let search_backend = search_backend.read().await;
+        complete_query(partial, search_backend.data())
+    };
+    let mut response = CreateAutocompleteResponse::new();
+    for name in suggestions {
+        response = response.add_string_choice(name.clone(), name);
+    }
+    interaction
+        .create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response))
+        .await?;
+    Ok(())
+}
+
+/// Suggests entry names, shortnames and category names for a partially typed query.
+///
+/// Names starting with the partial query come first, followed by names containing it, each in alphabetical order.
+/// Names too long to be suggested are left out, since shortening them would change the query.
+pub fn complete_query(partial: &str, from_data: &SearchBackendData) -> Vec<String> {
+    let partial = partial.trim().to_lowercase();
+    let names = from_data
+        .search_results
+        .iter()
+        .flat_map(|x| std::iter::once(&x.name).chain(x.shortname.iter()))
+        .chain(from_data.categories.iter().map(|x| &x.name));
+    let mut prefix_matches = Vec::<String>::new();
+    let mut other_matches = Vec::<String>::new();
+    for name in names.filter(|x| x.chars().count() <= AUTOCOMPLETE_NAME_LIMIT) {
+        let lower_name = name.to_lowercase();
+        let matches = if lower_name.starts_with(&partial) {
+            &mut prefix_matches
+        } else if lower_name.contains(&partial) {
+            &mut other_matches
+        } else {
+            continue;
+        };
+        if !matches.contains(name) {
+            matches.push(name.clone());
+        }
+    }
+    prefix_matches.sort_by_key(|x| x.to_lowercase());
+    other_matches.sort_by_key(|x| x.to_lowercase());
+    prefix_matches.extend(other_matches);
+    prefix_matches.truncate(AUTOCOMPLETE_LIMIT);
+    prefix_matches
+}
@@ -1,67 +1,260 @@
+use std::{
+    fs::read_to_string,
+    time::{Duration, UNIX_EPOCH},
+};
+
 use serenity::prelude::*;
 use serenity::{
+    builder::{CreateEmbed, CreateMessage},
     framework::standard::{
         macros::{command, group},
-        Args, CommandGroup, CommandOptions, CommandResult,
+        CommandResult,
     },
-    model::channel::Message,
+    model::{channel::Message, id::GuildId},
 };
 
+use crate::commands::admin::{
+    CMDCHAN_GROUP, NAVIGATION_COMMAND, PREFIX_COMMAND, RELOAD_COMMAND, REPORT_ALL_COMMAND, REPORT_COMMAND,
+};
+use crate::config::config;
 use crate::consts::*;
+use crate::search::backend::SearchDataKey;
+use crate::store::{stats::Stats, StoreKey};
+use crate::bot::{ShardManagerContainer, StartTimeKey};
+use crate::render::RenderableResponseKey;
 
-/// Container for non-admin-restricted utility commands.
+/// Container for utility commands. Admin-restricted commands from [`crate::commands::admin`] are registered here too,
+/// since the framework can only match one group per prefix.
 #[group]
 #[prefix = "!pax"]
-#[commands(about, help)]
+#[commands(about, diag, help, navigation, prefix, reload, report, report_all)]
+#[sub_groups(CmdChan)]
 pub struct CmdUtil;
 
-/// Helper function that can be used to print custom help text. Called by other commands.
-pub async fn print_help(ctx: &Context, msg: &Message) -> CommandResult {
-    msg.channel_id.say(&ctx.http, r#"
-```text
-*Search Commands*
-?pax                    Prints this help message.
-?pax [query]            Searches the paxbot tip database, returning any relevant results.
+/// Help text, as sections of commands and their descriptions. `{p}` is replaced with the guild's command prefix.
+const HELP_SECTIONS: &[(&str, &[(&str, &str)])] = &[
+    ("Search Commands", &[
+        ("{p}pax", "Prints this help message."),
+        ("{p}pax [query]", "Searches the paxbot tip database, returning any relevant results."),
+        ("/pax [query]", "Same as {p}pax [query], as a slash command."),
+        ("{p}pax cat:\"x\" -cat:\"y\" ...", "Only searches category x, leaving out category y."),
+        ("{p}pax \"phrase\" name:text", "Requires an exact phrase. name: only matches names and shortnames."),
+    ]),
+    ("Utility Commands", &[
+        ("{p}!pax about", "Prints information about bot version, stats, and how to contribute."),
+        ("{p}!pax diag", "Prints system diagnostic information."),
+        ("{p}!pax help", "Prints this help message."),
+    ]),
+    ("Server Admin Commands", &[
+        ("{p}!pax chan", "Lists the channels paxbot answers searches in."),
+        ("{p}!pax chan add [#ch]", "Answers searches in the mentioned channel. Once any are added, only those."),
+        ("{p}!pax chan remove [#ch]", "Stops answering searches in the mentioned channel."),
+        ("{p}!pax nav [mode]", "Sets who can navigate results: open (anyone) or requester (the default)."),
+        ("{p}!pax prefix [prefix]", "Sets the command prefix for this server, or reset to go back to the default."),
+        ("{p}!pax report [csv]", "Prints the most disliked results and queries that found nothing. Add csv for a file."),
+    ]),
+    ("Bot Owner Commands", &[
+        ("{p}!pax reload", "Reloads the tip database without restarting paxbot."),
+        ("{p}!pax reportall [csv]", "Prints the report for every server paxbot is in."),
+    ]),
+];
 
-*Utility Commands*
-?!pax about             Prints information about bot version, stats, and how to contribute.
-?!pax diag              Prints system diagnostic information.
-?!pax help              Prints this help message.
+/// Returns the command prefix for a guild, or the configured prefix outside guilds and in guilds without their own.
+pub async fn guild_prefix(ctx: &Context, guild_id: Option<GuildId>) -> String {
+    let guild_id = match guild_id {
+        Some(guild_id) => guild_id,
+        None => return config().bot.prefix.clone(),
+    };
+    let store = {
+        let ctx_data = ctx.data.read().await;
+        ctx_data.get::<StoreKey>().expect("State store missing.").clone()
+    };
+    let prefix_result = store.lock().await.prefix(guild_id);
+    match prefix_result {
+        Ok(Some(prefix)) => prefix,
+        Ok(None) => config().bot.prefix.clone(),
+        Err(err) => {
+            eprintln!("Failed to read the command prefix. {}", err);
+            config().bot.prefix.clone()
+        }
+    }
+}
 
-*Server Admin Commands*
-?!pax chan [channel]    Sets paxbot to only listen in the mentioned channel.
-```
-    "#).await?;
+/// Helper function that can be used to print custom help text. Called by other commands.
+pub async fn print_help(ctx: &Context, msg: &Message) -> CommandResult {
+    let prefix = guild_prefix(ctx, msg.guild_id).await;
+    let usage_width = HELP_SECTIONS
+        .iter()
+        .flat_map(|(_, commands)| commands.iter())
+        .map(|(usage, _)| usage.replace("{p}", &prefix).chars().count())
+        .max()
+        .unwrap_or_default();
+    let mut help_text = String::from("```text");
+    for (section, commands) in HELP_SECTIONS {
+        help_text.push_str(&format!("\n*{}*\n", section));
+        for (usage, description) in commands.iter() {
+            help_text.push_str(&format!(
+                "{:<width$} {}\n",
+                usage.replace("{p}", &prefix),
+                description.replace("{p}", &prefix),
+                width = usage_width
+            ));
+        }
+    }
+    help_text.push_str("```");
+    msg.channel_id.say(&ctx.http, help_text).await?;
     Ok(())
 }
 
 #[command]
 async fn about(ctx: &Context, msg: &Message) -> CommandResult {
-    msg.channel_id.send_message(&ctx.http, |m| {
-        m.embed(|e| {
-            e.title(format!("Paxbot v{}", PAXBOT_VERSION));
-            e.description(format!("`?pax` for help."));
-            e.fields(vec![
-                ("Users", "TODO", true),
-                ("Servers", "TODO", true),
-                ("Searches", "TODO", true),
-            ]);
-            e.fields(vec![
-                ("Contribute Code", "https://github.com/carriejv/paxbot", true),
-                ("Contribute Tips", "https://github.com/carriejv/paxbot/content", true),
-            ]);
-            e.fields(vec![
-                ("Maintainers", "Kali Liada @ Exodus", false),
-            ]);
-            e
-        });
-        m
-    }).await?;
+    let store = {
+        let ctx_data = ctx.data.read().await;
+        ctx_data.get::<StoreKey>().expect("State store missing.").clone()
+    };
+    let stats_result = store.lock().await.stats();
+    let stats = match stats_result {
+        Ok(stats) => stats,
+        Err(err) => {
+            eprintln!("Failed to read stats. {}", err);
+            Stats::default()
+        }
+    };
+    let searches = if stats.searches > 0 {
+        format!(
+            "{} ({:.0}% found)",
+            stats.searches,
+            stats.search_hits as f64 * 100f64 / stats.searches as f64
+        )
+    } else {
+        String::from("0")
+    };
+    let mut top_commands = stats
+        .commands
+        .iter()
+        .take(3)
+        .map(|(command, runs)| format!("`{}` {}", command, runs))
+        .collect::<Vec<String>>()
+        .join(", ");
+    if top_commands.is_empty() {
+        top_commands = String::from("None yet");
+    }
+    let guild_count = ctx.cache.guild_count();
+    let prefix = guild_prefix(ctx, msg.guild_id).await;
+    let embed = CreateEmbed::new()
+        .title(format!("Paxbot v{}", PAXBOT_VERSION))
+        .description(format!("`{}pax` for help.", prefix))
+        .fields(vec![
+            ("Users", stats.users.to_string(), true),
+            ("Servers", guild_count.to_string(), true),
+            ("Searches", searches, true),
+            ("Commands Run", stats.command_total().to_string(), true),
+        ])
+        .field("Most Used Commands", top_commands, false)
+        .fields(vec![
+            ("Contribute Code", "https://github.com/carriejv/paxbot", true),
+            ("Contribute Tips", "https://github.com/carriejv/paxbot/content", true),
+        ])
+        .fields(vec![
+            ("Maintainers", "Kali Liada @ Exodus", false),
+        ]);
+    msg.channel_id.send_message(&ctx.http, CreateMessage::new().embed(embed)).await?;
 
     Ok(())
 }
 
 #[command]
 async fn help(ctx: &Context, msg: &Message) -> CommandResult {
-    print_help(&ctx, &msg).await
+    print_help(ctx, msg).await
+}
+
+/// Prints gateway, process, and content diagnostics.
+#[command]
+async fn diag(ctx: &Context, msg: &Message) -> CommandResult {
+    let (shard_manager, start_time, search_backend, response_map) = {
+        let ctx_data = ctx.data.read().await;
+        (
+            ctx_data.get::<ShardManagerContainer>().expect("Shard manager missing.").clone(),
+            *ctx_data.get::<StartTimeKey>().expect("Start time missing."),
+            ctx_data.get::<SearchDataKey>().expect("Search data missing.").clone(),
+            ctx_data
+                .get::<RenderableResponseKey>()
+                .expect("Could not fetch renderable response map.")
+                .clone(),
+        )
+    };
+    let shard_status = {
+        let runners = shard_manager.runners.lock().await;
+        let mut shard_ids = runners.keys().copied().collect::<Vec<_>>();
+        shard_ids.sort_by_key(|x| x.0);
+        shard_ids
+            .iter()
+            .map(|shard_id| {
+                let runner = &runners[shard_id];
+                let latency = match runner.latency {
+                    Some(latency) => format!("{} ms", latency.as_millis()),
+                    None => String::from("no heartbeat yet"),
+                };
+                format!("Shard {}: {}, {}", shard_id.0, runner.stage, latency)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+    let (content_summary, content_version) = {
+        let search_backend = search_backend.read().await;
+        let search_data = search_backend.data();
+        let loaded_at = search_backend
+            .loaded_at()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0);
+        (
+            format!(
+                "{} categories, {} search results\n{} KiB of text",
+                search_data.categories.len(),
+                search_data.search_results.len(),
+                search_data.text_len() / 1024
+            ),
+            format!("Loaded <t:{}:F>\nHash `{:016x}`", loaded_at, search_data.content_hash()),
+        )
+    };
+    let cached_responses = response_map.lock().await.len();
+    let memory = match process_memory_kib() {
+        Some(memory) => format!("{:.1} MiB", memory as f64 / 1024f64),
+        None => String::from("Unknown"),
+    };
+    let embed = CreateEmbed::new()
+        .title(format!("Paxbot v{} Diagnostics", PAXBOT_VERSION))
+        .fields(vec![
+            ("Gateway", shard_status, false),
+            ("Uptime", format_duration(start_time.elapsed()), true),
+            ("Memory", memory, true),
+            ("Cached Responses", cached_responses.to_string(), true),
+            ("Search Data", content_summary, true),
+            ("Content", content_version, true),
+        ]);
+    msg.channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await?;
+    Ok(())
+}
+
+/// Returns the resident memory of this process in KiB. Only available on Linux.
+fn process_memory_kib() -> Option<u64> {
+    let status = read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    line.split_whitespace().nth(1)?.parse::<u64>().ok()
+}
+
+/// Formats a duration as days, hours, minutes and seconds, e.g. `2d 3h 4m 5s`.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, mins, secs) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if days > 0 {
+        format!("{}d {}h {}m {}s", days, hours, mins, secs)
+    } else if hours > 0 {
+        format!("{}h {}m {}s", hours, mins, secs)
+    } else {
+        format!("{}m {}s", mins, secs)
+    }
 }
@@ -0,0 +1,372 @@
+//! This file implements the bot configuration, read from `paxbot.toml` with `PAXBOT_*` environment overrides.
+//! Every setting has a default, so the file, and any section or setting in it, may be left out.
+
+use std::{
+    env,
+    error::Error,
+    fmt,
+    fs::read_to_string,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use serde::Deserialize;
+
+use crate::consts::PREFIX_MAX_LEN;
+
+/// Config file read when no `--config` flag is given. Unlike an explicitly given file, it may be missing.
+pub const CONFIG_PATH: &str = "./paxbot.toml";
+
+/// Configuration in use, set once at startup.
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Returns the configuration in use, or the defaults if none was loaded.
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// Makes `config` the configuration returned by [`config`]. Must be called before anything reads the configuration.
+pub fn init(config: Config) {
+    if CONFIG.set(config).is_err() {
+        panic!("Configuration was read before it was loaded.");
+    }
+}
+
+/// Splits `--config <path>` out of the command line arguments, returning the config path and the other arguments.
+pub fn parse_args() -> Result<(Option<PathBuf>, Vec<String>), String> {
+    let mut config_path = None;
+    let mut args = Vec::<String>::new();
+    let mut arg_iter = env::args().skip(1);
+    while let Some(arg) = arg_iter.next() {
+        if arg == "--config" {
+            match arg_iter.next() {
+                Some(path) => config_path = Some(PathBuf::from(path)),
+                None => return Err(String::from("--config needs the path to a config file.")),
+            }
+        } else if let Some(path) = arg.strip_prefix("--config=") {
+            config_path = Some(PathBuf::from(path));
+        } else {
+            args.push(arg);
+        }
+    }
+    Ok((config_path, args))
+}
+
+/// Returns true if `prefix` can be used as a command prefix.
+pub fn valid_prefix(prefix: &str) -> bool {
+    !prefix.is_empty()
+        && prefix.chars().count() <= PREFIX_MAX_LEN
+        && !prefix.contains(char::is_whitespace)
+        && !prefix.contains('`')
+}
+
+/// Bot configuration. See `paxbot.example.toml` for every setting and its environment override.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bot: BotConfig,
+    pub cache: CacheConfig,
+    pub content: ContentConfig,
+    pub emoji: EmojiConfig,
+    pub search: SearchConfig,
+    pub store: StoreConfig,
+}
+
+/// Command parsing settings.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotConfig {
+    /// Strings separating command arguments.
+    pub delimiters: Vec<String>,
+    /// Command prefix used in DMs, and in guilds that haven't set their own.
+    pub prefix: String,
+}
+
+impl Default for BotConfig {
+    fn default() -> BotConfig {
+        BotConfig {
+            delimiters: vec![String::from(","), String::from(" ")],
+            prefix: String::from("?"),
+        }
+    }
+}
+
+/// Navigable response cache settings.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Number of responses kept navigable.
+    pub size: usize,
+    /// Seconds a response stays navigable.
+    pub ttl: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> CacheConfig {
+        CacheConfig { size: 1000, ttl: 86400 }
+    }
+}
+
+/// Search data storage.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentBackend {
+    /// A SQLite database, at `content.sqlite_path`.
+    Sqlite,
+    /// TOML or YAML content files, under `content.path`.
+    Toml,
+}
+
+impl FromStr for ContentBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ContentBackend, String> {
+        match s {
+            "sqlite" => Ok(ContentBackend::Sqlite),
+            "toml" => Ok(ContentBackend::Toml),
+            other => Err(format!("unknown backend `{}`, use toml or sqlite", other)),
+        }
+    }
+}
+
+/// Search data settings.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContentConfig {
+    /// Which backend search data is loaded from.
+    pub backend: ContentBackend,
+    /// Content file or directory for the toml backend, and the source for `--import-toml`.
+    pub path: PathBuf,
+    /// Database for the sqlite backend.
+    pub sqlite_path: PathBuf,
+}
+
+impl Default for ContentConfig {
+    fn default() -> ContentConfig {
+        ContentConfig {
+            backend: ContentBackend::Toml,
+            path: PathBuf::from("./content"),
+            sqlite_path: PathBuf::from("./content/content.db"),
+        }
+    }
+}
+
+/// Reactions used for feedback and navigation.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmojiConfig {
+    /// Marks a response as unhelpful.
+    pub feedback_bad: String,
+    /// Marks a response as helpful.
+    pub feedback_good: String,
+    /// Shows the previous result, when buttons aren't available.
+    pub results_backward: String,
+    /// Shows the next result, when buttons aren't available.
+    pub results_forward: String,
+}
+
+impl Default for EmojiConfig {
+    fn default() -> EmojiConfig {
+        EmojiConfig {
+            feedback_bad: String::from("💢"),
+            feedback_good: String::from("❤️"),
+            results_backward: String::from("⬅️"),
+            results_forward: String::from("➡️"),
+        }
+    }
+}
+
+/// Search scoring settings. Scores are between 0 and 1.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    /// Minimum name score for a result to be returned.
+    pub score_threshold: f32,
+    /// Minimum name score for a "Did you mean" suggestion.
+    pub suggest_threshold: f32,
+    /// Share of the body text score added to name matches, breaking ties between similarly named results.
+    pub text_blend: f32,
+    /// Minimum normalized body text score for a body-only match to be returned as a result.
+    pub text_threshold: f32,
+    /// Multiplier applied to body-only matches. Must not exceed `score_threshold` so name hits always rank higher.
+    pub text_weight: f32,
+}
+
+impl Default for SearchConfig {
+    fn default() -> SearchConfig {
+        SearchConfig {
+            score_threshold: 0.6,
+            suggest_threshold: 0.2,
+            text_blend: 0.1,
+            text_threshold: 0.3,
+            text_weight: 0.5,
+        }
+    }
+}
+
+/// State store settings.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoreConfig {
+    /// Database holding feedback, settings, stats and navigable responses.
+    pub path: PathBuf,
+}
+
+impl Default for StoreConfig {
+    fn default() -> StoreConfig {
+        StoreConfig {
+            path: PathBuf::from("./paxbot.db"),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config file at `path`, or [`CONFIG_PATH`] if it exists, then applies environment overrides and
+    /// validates the result.
+    pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+        let path = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => Some(PathBuf::from(CONFIG_PATH)).filter(|path| path.exists()),
+        };
+        let mut config = match path {
+            Some(path) => {
+                let text = read_to_string(&path).map_err(|err| ConfigError::Io(path.clone(), err))?;
+                toml::from_str::<Config>(&text).map_err(|err| ConfigError::Parse(path, err.to_string()))?
+            }
+            None => Config::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Replaces settings with any `PAXBOT_*` environment variables that are set.
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        // Each character is a delimiter, since a list doesn't fit neatly in a variable.
+        if let Ok(delimiters) = env::var("PAXBOT_DELIMITERS") {
+            self.bot.delimiters = delimiters.chars().map(String::from).collect();
+        }
+        env_override("PAXBOT_PREFIX", &mut self.bot.prefix)?;
+        env_override("PAXBOT_CACHE_SIZE", &mut self.cache.size)?;
+        env_override("PAXBOT_CACHE_TTL", &mut self.cache.ttl)?;
+        env_override("PAXBOT_BACKEND", &mut self.content.backend)?;
+        env_override("PAXBOT_CONTENT_PATH", &mut self.content.path)?;
+        env_override("PAXBOT_CONTENT_SQLITE_PATH", &mut self.content.sqlite_path)?;
+        env_override("PAXBOT_EMOJI_FEEDBACK_BAD", &mut self.emoji.feedback_bad)?;
+        env_override("PAXBOT_EMOJI_FEEDBACK_GOOD", &mut self.emoji.feedback_good)?;
+        env_override("PAXBOT_EMOJI_RESULTS_BACKWARD", &mut self.emoji.results_backward)?;
+        env_override("PAXBOT_EMOJI_RESULTS_FORWARD", &mut self.emoji.results_forward)?;
+        env_override("PAXBOT_SEARCH_SCORE_THRESHOLD", &mut self.search.score_threshold)?;
+        env_override("PAXBOT_SEARCH_SUGGEST_THRESHOLD", &mut self.search.suggest_threshold)?;
+        env_override("PAXBOT_SEARCH_TEXT_BLEND", &mut self.search.text_blend)?;
+        env_override("PAXBOT_SEARCH_TEXT_THRESHOLD", &mut self.search.text_threshold)?;
+        env_override("PAXBOT_SEARCH_TEXT_WEIGHT", &mut self.search.text_weight)?;
+        env_override("PAXBOT_STORE_PATH", &mut self.store.path)?;
+        Ok(())
+    }
+
+    /// Checks every setting, reporting all problems at once.
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::<String>::new();
+        if !valid_prefix(&self.bot.prefix) {
+            problems.push(format!(
+                "bot.prefix `{}` must be 1 to {} characters, without spaces or backticks.",
+                self.bot.prefix, PREFIX_MAX_LEN
+            ));
+        }
+        if self.bot.delimiters.is_empty() || self.bot.delimiters.iter().any(String::is_empty) {
+            problems.push(String::from(
+                "bot.delimiters must list at least one delimiter, none of them empty.",
+            ));
+        }
+        if self.cache.size == 0 {
+            problems.push(String::from("cache.size must be at least 1."));
+        }
+        if self.cache.ttl == 0 {
+            problems.push(String::from("cache.ttl must be at least 1 second."));
+        }
+        let emoji = [
+            ("emoji.feedback_bad", &self.emoji.feedback_bad),
+            ("emoji.feedback_good", &self.emoji.feedback_good),
+            ("emoji.results_backward", &self.emoji.results_backward),
+            ("emoji.results_forward", &self.emoji.results_forward),
+        ];
+        for (index, (name, value)) in emoji.iter().enumerate() {
+            if value.trim().is_empty() {
+                problems.push(format!("{} must not be empty.", name));
+            } else if let Some((other, _)) = emoji[..index].iter().find(|(_, other_value)| other_value == value) {
+                problems.push(format!("{} is the same as {}.", name, other));
+            }
+        }
+        let scores = [
+            ("search.score_threshold", self.search.score_threshold),
+            ("search.suggest_threshold", self.search.suggest_threshold),
+            ("search.text_blend", self.search.text_blend),
+            ("search.text_threshold", self.search.text_threshold),
+            ("search.text_weight", self.search.text_weight),
+        ];
+        for (name, value) in scores.iter() {
+            if !(0f32..=1f32).contains(value) {
+                problems.push(format!("{} is {}, but must be between 0 and 1.", name, value));
+            }
+        }
+        if self.search.suggest_threshold > self.search.score_threshold {
+            problems.push(String::from(
+                "search.suggest_threshold must not be greater than search.score_threshold.",
+            ));
+        }
+        if self.search.text_weight > self.search.score_threshold {
+            problems.push(String::from(
+                "search.text_weight must not be greater than search.score_threshold, or body text matches could \
+                 outrank name matches.",
+            ));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+/// Replaces `setting` with the parsed value of `var`, if it is set.
+fn env_override<T>(var: &'static str, setting: &mut T) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    if let Ok(value) = env::var(var) {
+        *setting = value
+            .parse()
+            .map_err(|err: T::Err| ConfigError::Env(var, value.clone(), err.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Errors raised while loading the configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// An environment override could not be parsed.
+    Env(&'static str, String, String),
+    /// Settings were loaded, but some are invalid.
+    Invalid(Vec<String>),
+    /// The config file could not be read.
+    Io(PathBuf, io::Error),
+    /// The config file was read, but could not be parsed.
+    Parse(PathBuf, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Env(var, value, err) => write!(f, "Invalid {} `{}`: {}", var, value, err),
+            ConfigError::Invalid(problems) => write!(f, "Invalid configuration:\n{}", problems.join("\n")),
+            ConfigError::Io(path, err) => write!(f, "Failed to read {}: {}", path.display(), err),
+            ConfigError::Parse(path, err) => write!(f, "Failed to parse {}: {}", path.display(), err),
+        }
+    }
+}
+
+impl Error for ConfigError {}
@@ -1,10 +1,25 @@
 pub const PAXBOT_VERSION: &str = "0.1.0";
 
-pub const REACT_RESULTS_FORWARD: &str = "➡️";
-pub const REACT_RESULTS_BACKWARD: &str = "⬅️";
+/// Longest command prefix a guild may set, in characters.
+pub const PREFIX_MAX_LEN: usize = 8;
 
-pub const REACT_FEEDBACK_GOOD: &str = "❤️";
-pub const REACT_FEEDBACK_BAD: &str = "💢";
+pub const EMBED_TITLE_LIMIT: usize = 256;
+pub const EMBED_DESCRIPTION_LIMIT: usize = 4096;
+pub const EMBED_FIELD_NAME_LIMIT: usize = 256;
+pub const EMBED_FIELD_VALUE_LIMIT: usize = 1024;
+pub const EMBED_FIELD_COUNT_LIMIT: usize = 25;
+pub const EMBED_FOOTER_LIMIT: usize = 2048;
+pub const EMBED_TOTAL_LIMIT: usize = 6000;
+pub const MESSAGE_CONTENT_LIMIT: usize = 2000;
 
-pub const SEARCH_SCORE_THRESHOLD: f32 = 0.6;
-pub const SEARCH_SUGGEST_THRESHOLD: f32 = 0.2;
+/// Members listed on each page of a category, one per number reaction.
+pub const CATEGORY_PAGE_MEMBERS: usize = 10;
+
+/// Seconds between sweeps for expired responses.
+pub const RESPONSE_CACHE_SWEEP: u64 = 60;
+
+/// Rows per report section shown in Discord, and rows per page of a section.
+pub const REPORT_ROW_LIMIT: usize = 50;
+pub const REPORT_PAGE_ROWS: usize = 10;
+/// Rows per report section exported to CSV.
+pub const REPORT_CSV_ROW_LIMIT: usize = 1000;
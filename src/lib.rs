@@ -0,0 +1,15 @@
+//! paxbot searches a database of tips and answers on Discord. The bot, and the tools for working on its content, are
+//! small binaries on top of this library.
+
+// Prefix commands still use serenity's standard framework, which is deprecated in favour of application commands.
+#[allow(deprecated)]
+pub mod bot;
+pub mod cache;
+#[allow(deprecated)]
+pub mod commands;
+pub mod config;
+mod consts;
+pub mod lint;
+pub mod render;
+pub mod search;
+pub mod store;
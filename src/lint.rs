@@ -0,0 +1,624 @@
+//! This file implements content checks for contributors, run with `paxbot --check [path]`.
+//! Problems are reported with the location of the offending entry, compiler style.
+
+use std::{collections::HashMap, fmt, path::Path};
+
+use rust_fuzzy_search::fuzzy_compare;
+
+use crate::config::config;
+use crate::consts::*;
+use crate::render::RenderableMessage;
+use crate::search::backend::{describe_source, file::load_path, SearchBackendData, SourceLocation};
+use crate::search::{category_pages, CategoryResult, RenderType, SearchResponse, SearchResult};
+
+/// A single problem found in search data.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LintIssue {
+    /// Where the offending entry was defined, if known.
+    pub location: Option<SourceLocation>,
+    /// Description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{}: {}", location, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Loads the content file or directory at `path` and prints any problems found. Returns the process exit code.
+pub fn run_check(path: &Path) -> i32 {
+    let search_data = match load_path(path) {
+        Ok(search_data) => search_data,
+        Err(why) => {
+            eprintln!("{}", why);
+            return 1;
+        }
+    };
+    let issues = lint(&search_data);
+    for issue in &issues {
+        eprintln!("{}", issue);
+    }
+    if issues.is_empty() {
+        println!(
+            "{}: {} categories and {} search results, no problems found.",
+            path.display(),
+            search_data.categories.len(),
+            search_data.search_results.len()
+        );
+        0
+    } else {
+        eprintln!("{} problem(s) found.", issues.len());
+        1
+    }
+}
+
+/// Checks search data for problems that would silently break searches or fail to render.
+pub fn lint(search_data: &SearchBackendData) -> Vec<LintIssue> {
+    let mut issues = Vec::<LintIssue>::new();
+    check_categories(search_data, &mut issues);
+    check_names(search_data, &mut issues);
+    check_links(search_data, &mut issues);
+    check_render_limits(search_data, &mut issues);
+    issues.sort_by(|a, b| match (&a.location, &b.location) {
+        (Some(a_loc), Some(b_loc)) => (&a_loc.path, a_loc.line).cmp(&(&b_loc.path, b_loc.line)),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+    issues
+}
+
+/// Reports undefined categories on items, and categories without any members.
+fn check_categories(search_data: &SearchBackendData, issues: &mut Vec<LintIssue>) {
+    for search_item in &search_data.search_results {
+        for category in &search_item.categories {
+            if search_data.categories.iter().any(|x| &x.name == category) {
+                continue;
+            }
+            let mut message = format!(
+                "search result `{}` lists undefined category `{}`.",
+                search_item.name, category
+            );
+            let best_guess = search_data
+                .categories
+                .iter()
+                .map(|x| (fuzzy_compare(&x.name.to_lowercase(), &category.to_lowercase()), &x.name))
+                .filter(|(score, _)| *score > config().search.suggest_threshold)
+                .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+            if let Some((_, guess)) = best_guess {
+                message.push_str(&format!(" Did you mean `{}`?", guess));
+            }
+            issues.push(LintIssue {
+                location: search_item.source.clone(),
+                message,
+            });
+        }
+    }
+    for category_item in &search_data.categories {
+        if !search_data
+            .search_results
+            .iter()
+            .any(|x| x.categories.contains(&category_item.name))
+        {
+            issues.push(LintIssue {
+                location: category_item.source.clone(),
+                message: format!("category `{}` has no members.", category_item.name),
+            });
+        }
+    }
+}
+
+/// Reports names and shortnames that are used by more than one entry, ignoring case.
+fn check_names(search_data: &SearchBackendData, issues: &mut Vec<LintIssue>) {
+    let mut category_names = HashMap::<String, (&str, &Option<SourceLocation>)>::new();
+    for category_item in &search_data.categories {
+        let name = category_item.name.to_lowercase();
+        if let Some((other, other_source)) = category_names.get(&name) {
+            issues.push(LintIssue {
+                location: category_item.source.clone(),
+                message: format!(
+                    "category `{}` has the same name as category `{}` at {}.",
+                    category_item.name,
+                    other,
+                    describe_source(other_source)
+                ),
+            });
+        } else {
+            category_names.insert(name, (&category_item.name, &category_item.source));
+        }
+    }
+    let mut item_names = HashMap::<String, (&str, &Option<SourceLocation>)>::new();
+    for search_item in &search_data.search_results {
+        let mut names = search_item
+            .shortname
+            .iter()
+            .map(|x| x.to_lowercase())
+            .collect::<Vec<String>>();
+        names.push(search_item.name.to_lowercase());
+        names.sort();
+        names.dedup();
+        for name in names {
+            if let Some((other, other_source)) = item_names.get(&name) {
+                issues.push(LintIssue {
+                    location: search_item.source.clone(),
+                    message: format!(
+                        "search result `{}` uses the name `{}`, which is already used by `{}` at {}.",
+                        search_item.name,
+                        name,
+                        other,
+                        describe_source(other_source)
+                    ),
+                });
+            } else {
+                item_names.insert(name, (&search_item.name, &search_item.source));
+            }
+        }
+    }
+}
+
+/// Reports external links that are neither a bare URL nor a well formed markdown `[text](url)` link.
+fn check_links(search_data: &SearchBackendData, issues: &mut Vec<LintIssue>) {
+    for search_item in &search_data.search_results {
+        for link in &search_item.ext_links {
+            if let Err(why) = check_link(link) {
+                issues.push(LintIssue {
+                    location: search_item.source.clone(),
+                    message: format!(
+                        "search result `{}` has a malformed link `{}`: {}",
+                        search_item.name, link, why
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// Checks a single external link.
+fn check_link(link: &str) -> Result<(), &'static str> {
+    let link = link.trim();
+    if !link.starts_with('[') {
+        return check_url(link);
+    }
+    let (text, rest) = match link[1..].split_once("](") {
+        Some(parts) => parts,
+        None => return Err("expected `[text](url)`."),
+    };
+    if text.trim().is_empty() {
+        return Err("link text is empty.");
+    }
+    if text.contains('[') || text.contains(']') {
+        return Err("link text contains brackets.");
+    }
+    match rest.strip_suffix(')') {
+        Some(url) => check_url(url),
+        None => Err("expected the link to end with `)`."),
+    }
+}
+
+/// Checks that a URL is absolute and contains no whitespace.
+fn check_url(url: &str) -> Result<(), &'static str> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err("URL must start with http:// or https://.");
+    }
+    if url.contains(char::is_whitespace) || url.contains(')') {
+        return Err("URL contains whitespace or a stray `)`.");
+    }
+    Ok(())
+}
+
+/// Renders every entry the way a search matching all of them would, and reports embeds Discord would reject.
+fn check_render_limits(search_data: &SearchBackendData, issues: &mut Vec<LintIssue>) {
+    let search_response = SearchResponse {
+        category_results: search_data
+            .categories
+            .iter()
+            .map(|category_item| CategoryResult {
+                members: search_data
+                    .search_results
+                    .iter()
+                    .filter(|x| x.categories.contains(&category_item.name))
+                    .map(|x| x.name.clone())
+                    .collect::<Vec<String>>(),
+                name: category_item.name.clone(),
+                score: 1f32,
+                text: category_item.text.clone(),
+            })
+            .collect(),
+        member_results: Vec::new(),
+        query: String::new(),
+        render_type: RenderType::Result,
+        results: search_data
+            .search_results
+            .iter()
+            .map(|search_item| SearchResult {
+                categories: search_item.categories.clone(),
+                ext_links: search_item.ext_links.clone(),
+                name: search_item.name.clone(),
+                score: 1f32,
+                shortname: search_item.shortname.clone(),
+                text: search_item.text.clone(),
+            })
+            .collect(),
+    };
+    // Results render first, followed by categories, which may span several pages of members each.
+    let sources = search_data
+        .search_results
+        .iter()
+        .map(|x| (format!("search result `{}`", x.name), &x.source))
+        .chain(search_data.categories.iter().flat_map(|x| {
+            let page_count = search_response
+                .category_results
+                .iter()
+                .find(|category| category.name == x.name)
+                .map(|category| category_pages(category.members.len()))
+                .unwrap_or(1);
+            std::iter::repeat_n((format!("category `{}`", x.name), &x.source), page_count)
+        }));
+    // Oversized text is split into parts when rendered, so check each part rather than the whole message.
+    for (message, (entry, source)) in search_response.get_renderable_messages().iter().zip(sources) {
+        for part in message.split() {
+            check_embed_limits(&part, &entry, source, issues);
+        }
+    }
+}
+
+/// Reports every way a single rendered message breaks Discord's embed limits.
+fn check_embed_limits(
+    message: &RenderableMessage, entry: &str, source: &Option<SourceLocation>, issues: &mut Vec<LintIssue>,
+) {
+    let embed = match &message.embed {
+        Some(embed) => embed,
+        None => return,
+    };
+    let mut problems = Vec::<String>::new();
+    let title_len = embed.title.chars().count();
+    if title_len > EMBED_TITLE_LIMIT {
+        problems.push(format!(
+            "title is {} characters (limit {})",
+            title_len, EMBED_TITLE_LIMIT
+        ));
+    }
+    if let Some(desc) = &embed.description {
+        let desc_len = desc.chars().count();
+        if desc_len > EMBED_DESCRIPTION_LIMIT {
+            problems.push(format!(
+                "description is {} characters (limit {})",
+                desc_len, EMBED_DESCRIPTION_LIMIT
+            ));
+        }
+    }
+    for (name, value, _) in embed.fields.iter().flatten() {
+        let value_len = value.trim().chars().count();
+        if value_len == 0 {
+            problems.push(format!("field `{}` is empty", name));
+        } else if value_len > EMBED_FIELD_VALUE_LIMIT {
+            problems.push(format!(
+                "field `{}` is {} characters (limit {})",
+                name, value_len, EMBED_FIELD_VALUE_LIMIT
+            ));
+        }
+        let name_len = name.chars().count();
+        if name_len > EMBED_FIELD_NAME_LIMIT {
+            problems.push(format!(
+                "field name `{}` is too long (limit {})",
+                name, EMBED_FIELD_NAME_LIMIT
+            ));
+        }
+    }
+    if let Some(footer_text) = &embed.footer {
+        let footer_len = footer_text.chars().count();
+        if footer_len > EMBED_FOOTER_LIMIT {
+            problems.push(format!(
+                "footer is {} characters (limit {})",
+                footer_len, EMBED_FOOTER_LIMIT
+            ));
+        }
+    }
+    let total_len = embed.total_len();
+    if total_len > EMBED_TOTAL_LIMIT {
+        problems.push(format!(
+            "embed is {} characters in total (limit {})",
+            total_len, EMBED_TOTAL_LIMIT
+        ));
+    }
+    for problem in problems {
+        issues.push(LintIssue {
+            location: source.clone(),
+            message: format!("{} will fail to render: {}.", entry, problem),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::render::RenderableEmbed;
+    use crate::search::backend::{SearchBackendCategory, SearchBackendItem};
+
+    fn at(line: usize) -> Option<SourceLocation> {
+        Some(SourceLocation {
+            line,
+            path: PathBuf::from("content.toml"),
+        })
+    }
+
+    fn item(name: &str, categories: &[&str], line: usize) -> SearchBackendItem {
+        SearchBackendItem {
+            categories: categories.iter().map(|x| x.to_string()).collect(),
+            ext_links: Vec::new(),
+            name: name.to_string(),
+            shortname: Vec::new(),
+            source: at(line),
+            text: String::from("Spread out before each landing."),
+        }
+    }
+
+    fn category(name: &str, line: usize) -> SearchBackendCategory {
+        SearchBackendCategory {
+            name: name.to_string(),
+            source: at(line),
+            text: String::from("Harder versions of the raids."),
+        }
+    }
+
+    fn data(categories: Vec<SearchBackendCategory>, search_results: Vec<SearchBackendItem>) -> SearchBackendData {
+        SearchBackendData {
+            categories,
+            search_results,
+        }
+    }
+
+    fn messages(issues: &[LintIssue]) -> Vec<String> {
+        issues.iter().map(ToString::to_string).collect()
+    }
+
+    fn embed_problems(embed: RenderableEmbed) -> Vec<String> {
+        let message = RenderableMessage {
+            category_index: None,
+            content: String::new(),
+            embed: Some(embed),
+            member_only: false,
+            members: Vec::new(),
+            result: None,
+        };
+        let mut issues = Vec::<LintIssue>::new();
+        check_embed_limits(&message, "search result `Titan`", &at(7), &mut issues);
+        messages(&issues)
+    }
+
+    fn embed() -> RenderableEmbed {
+        RenderableEmbed {
+            description: Some(String::from("Savage Raids")),
+            fields: Some(vec![(
+                String::from("Information"),
+                String::from("Spread out before each landing."),
+                false,
+            )]),
+            footer: Some(String::from("Use ❤️ if paxbot found what you needed.")),
+            title: String::from("Titan"),
+        }
+    }
+
+    #[test]
+    fn clean_data_has_no_issues() {
+        let search_data = data(
+            vec![category("Savage Raids", 1)],
+            vec![item("Titan", &["Savage Raids"], 5)],
+        );
+        assert!(lint(&search_data).is_empty());
+    }
+
+    #[test]
+    fn reports_undefined_categories_with_a_suggestion() {
+        let search_data = data(
+            vec![category("Savage Raids", 1)],
+            vec![item("Titan", &["Savage Raid"], 5)],
+        );
+        assert_eq!(
+            messages(&lint(&search_data)),
+            vec![
+                "content.toml:1: category `Savage Raids` has no members.",
+                "content.toml:5: search result `Titan` lists undefined category `Savage Raid`. Did you mean `Savage Raids`?",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_undefined_categories_without_a_close_match() {
+        let search_data = data(
+            vec![category("Savage Raids", 1)],
+            vec![item("Titan", &["Savage Raids", "zzzz"], 5)],
+        );
+        assert_eq!(
+            messages(&lint(&search_data)),
+            vec!["content.toml:5: search result `Titan` lists undefined category `zzzz`."]
+        );
+    }
+
+    #[test]
+    fn reports_empty_categories() {
+        let search_data = data(
+            vec![category("Savage Raids", 1), category("Trials", 3)],
+            vec![item("Titan", &["Savage Raids"], 5)],
+        );
+        assert_eq!(
+            messages(&lint(&search_data)),
+            vec!["content.toml:3: category `Trials` has no members."]
+        );
+    }
+
+    #[test]
+    fn reports_category_names_differing_only_in_case() {
+        let search_data = data(
+            vec![category("Savage Raids", 1), category("savage raids", 3)],
+            vec![item("Titan", &["Savage Raids", "savage raids"], 5)],
+        );
+        assert_eq!(
+            messages(&lint(&search_data)),
+            vec!["content.toml:3: category `savage raids` has the same name as category `Savage Raids` at content.toml:1."]
+        );
+    }
+
+    #[test]
+    fn reports_names_colliding_with_other_shortnames() {
+        let mut titan = item("Titan", &["Savage Raids"], 5);
+        titan.shortname = vec![String::from("T1")];
+        let search_data = data(
+            vec![category("Savage Raids", 1)],
+            vec![titan, item("t1", &["Savage Raids"], 9)],
+        );
+        assert_eq!(
+            messages(&lint(&search_data)),
+            vec!["content.toml:9: search result `t1` uses the name `t1`, which is already used by `Titan` at content.toml:5."]
+        );
+    }
+
+    #[test]
+    fn shortnames_matching_their_own_name_are_not_collisions() {
+        let mut titan = item("Titan", &["Savage Raids"], 5);
+        titan.shortname = vec![String::from("TITAN")];
+        let search_data = data(vec![category("Savage Raids", 1)], vec![titan]);
+        assert!(lint(&search_data).is_empty());
+    }
+
+    #[test]
+    fn accepts_bare_urls_and_markdown_links() {
+        assert_eq!(check_link("https://example.com/titan"), Ok(()));
+        assert_eq!(check_link(" [Guide](http://example.com/titan) "), Ok(()));
+    }
+
+    #[test]
+    fn rejects_malformed_links() {
+        assert_eq!(
+            check_link("example.com"),
+            Err("URL must start with http:// or https://.")
+        );
+        assert_eq!(
+            check_link("https://example.com/a b"),
+            Err("URL contains whitespace or a stray `)`.")
+        );
+        assert_eq!(
+            check_link("[Guide] (https://example.com)"),
+            Err("expected `[text](url)`.")
+        );
+        assert_eq!(check_link("[ ](https://example.com)"), Err("link text is empty."));
+        assert_eq!(
+            check_link("[[Guide]](https://example.com)"),
+            Err("link text contains brackets.")
+        );
+        assert_eq!(
+            check_link("[Guide](https://example.com"),
+            Err("expected the link to end with `)`.")
+        );
+        assert_eq!(
+            check_link("[Guide](https://example.com))"),
+            Err("URL contains whitespace or a stray `)`.")
+        );
+        assert_eq!(
+            check_link("[Guide](example.com)"),
+            Err("URL must start with http:// or https://.")
+        );
+    }
+
+    #[test]
+    fn reports_malformed_links_at_their_entry() {
+        let mut titan = item("Titan", &["Savage Raids"], 5);
+        titan.ext_links = vec![String::from("[Guide](example.com)")];
+        let search_data = data(vec![category("Savage Raids", 1)], vec![titan]);
+        assert_eq!(
+            messages(&lint(&search_data)),
+            vec!["content.toml:5: search result `Titan` has a malformed link `[Guide](example.com)`: URL must start with http:// or https://."]
+        );
+    }
+
+    #[test]
+    fn embeds_within_limits_pass() {
+        assert!(embed_problems(embed()).is_empty());
+    }
+
+    #[test]
+    fn reports_long_titles() {
+        let mut long_embed = embed();
+        long_embed.title = "a".repeat(EMBED_TITLE_LIMIT + 1);
+        assert_eq!(
+            embed_problems(long_embed),
+            vec!["content.toml:7: search result `Titan` will fail to render: title is 257 characters (limit 256)."]
+        );
+    }
+
+    #[test]
+    fn reports_long_descriptions() {
+        let mut long_embed = embed();
+        long_embed.description = Some("a".repeat(EMBED_DESCRIPTION_LIMIT + 1));
+        assert_eq!(
+            embed_problems(long_embed),
+            vec!["content.toml:7: search result `Titan` will fail to render: description is 4097 characters (limit 4096)."]
+        );
+    }
+
+    #[test]
+    fn reports_empty_and_long_fields() {
+        let mut long_embed = embed();
+        long_embed.fields = Some(vec![
+            (String::from("Information"), String::from(" "), false),
+            (
+                String::from("External Links"),
+                "a".repeat(EMBED_FIELD_VALUE_LIMIT + 1),
+                false,
+            ),
+            (
+                "a".repeat(EMBED_FIELD_NAME_LIMIT + 1),
+                String::from("Spread out."),
+                false,
+            ),
+        ]);
+        let problems = embed_problems(long_embed);
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].ends_with("field `Information` is empty."));
+        assert!(problems[1].ends_with("field `External Links` is 1025 characters (limit 1024)."));
+        assert!(problems[2].ends_with("is too long (limit 256)."));
+    }
+
+    #[test]
+    fn reports_long_footers() {
+        let mut long_embed = embed();
+        long_embed.footer = Some("a".repeat(EMBED_FOOTER_LIMIT + 1));
+        assert_eq!(
+            embed_problems(long_embed),
+            vec!["content.toml:7: search result `Titan` will fail to render: footer is 2049 characters (limit 2048)."]
+        );
+    }
+
+    #[test]
+    fn reports_long_embeds() {
+        let mut long_embed = embed();
+        long_embed.description = Some("a".repeat(EMBED_DESCRIPTION_LIMIT));
+        long_embed.footer = Some("a".repeat(EMBED_FOOTER_LIMIT));
+        let problems = embed_problems(long_embed);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].ends_with("characters in total (limit 6000)."));
+    }
+
+    #[test]
+    fn reports_render_limits_at_the_offending_entry() {
+        let search_data = data(
+            vec![category("Savage Raids", 1)],
+            vec![
+                item("Titan", &["Savage Raids"], 5),
+                item(&"a".repeat(300), &["Savage Raids"], 9),
+            ],
+        );
+        let issues = lint(&search_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].location, at(9));
+        assert!(issues[0]
+            .message
+            .ends_with("will fail to render: title is 303 characters (limit 256)."));
+    }
+}
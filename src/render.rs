@@ -0,0 +1,455 @@
+//! This file implements responses rendered from search results, and navigating them on Discord.
+//! Messages are turned into output by a [`Renderer`], so the same response can be shown on Discord or elsewhere.
+
+use std::{sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serenity::prelude::*;
+use serenity::{
+    builder::{
+        CreateActionRow, CreateButton, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, EditMessage,
+    },
+    http::Http,
+    model::{
+        application::{ButtonStyle, ComponentInteractionData, ComponentInteractionDataKind},
+        channel::{Message, ReactionType},
+        id::{ChannelId, MessageId, UserId},
+    },
+};
+use tokio::sync::Mutex;
+
+use crate::cache::LruCache;
+use crate::config::config;
+use crate::consts::{CATEGORY_PAGE_MEMBERS, MESSAGE_CONTENT_LIMIT, RESPONSE_CACHE_SWEEP};
+use crate::store::{unix_now, Store, StoreKey};
+
+pub mod embed;
+use embed::EmbedRenderer;
+pub mod json;
+pub mod split;
+pub mod text;
+use text::TextRenderer;
+
+/// Custom id of the menu selecting a member listed on a category page.
+const MEMBER_MENU_ID: &str = "pax:member";
+/// Longest label Discord accepts for a select menu option, in characters.
+const MEMBER_MENU_LABEL_LIMIT: usize = 100;
+/// Reactions selecting the members listed on a category page, in list order.
+const MEMBER_REACTIONS: [&str; CATEGORY_PAGE_MEMBERS] = [
+    "1\u{fe0f}\u{20e3}",
+    "2\u{fe0f}\u{20e3}",
+    "3\u{fe0f}\u{20e3}",
+    "4\u{fe0f}\u{20e3}",
+    "5\u{fe0f}\u{20e3}",
+    "6\u{fe0f}\u{20e3}",
+    "7\u{fe0f}\u{20e3}",
+    "8\u{fe0f}\u{20e3}",
+    "9\u{fe0f}\u{20e3}",
+    "\u{1f51f}",
+];
+
+/// Turns a [`RenderableMessage`] into output for a particular target.
+pub trait Renderer {
+    /// Rendered form of a single message.
+    type Output;
+
+    /// Renders a single message.
+    fn render(&self, message: &RenderableMessage) -> Self::Output;
+}
+
+/// Defines data that can be rendered to an embed message.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RenderableEmbed {
+    /// Embed description
+    pub description: Option<String>,
+    /// Fields as (title, content, inline) tuples
+    pub fields: Option<Vec<(String, String, bool)>>,
+    /// Embed footer
+    pub footer: Option<String>,
+    /// Embed title
+    pub title: String,
+}
+
+impl RenderableEmbed {
+    /// Returns the number of characters Discord counts towards the total embed size limit.
+    pub fn total_len(&self) -> usize {
+        let mut total_len = self.title.chars().count();
+        if let Some(desc) = &self.description {
+            total_len += desc.chars().count();
+        }
+        if let Some(fields) = &self.fields {
+            for (name, value, _) in fields {
+                total_len += name.chars().count() + value.chars().count();
+            }
+        }
+        if let Some(footer_text) = &self.footer {
+            total_len += footer_text.chars().count();
+        }
+        total_len
+    }
+}
+
+/// Moves through a [`RenderableResponse`] to navigate it, from a button or a reaction.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Navigation {
+    /// Previous message, wrapping around to the last
+    Back,
+    /// Category of the current result
+    Category,
+    /// First message
+    First,
+    /// Next message, wrapping around to the first
+    Forward,
+    /// Last message
+    Last,
+    /// Result of the member at this position in the current message's member list
+    Member(usize),
+}
+
+impl Navigation {
+    /// Navigation buttons, in display order.
+    const BUTTONS: [Navigation; 5] = [
+        Navigation::First,
+        Navigation::Back,
+        Navigation::Category,
+        Navigation::Forward,
+        Navigation::Last,
+    ];
+
+    /// Returns the custom id of this navigation's button.
+    fn custom_id(self) -> &'static str {
+        match self {
+            Navigation::Back => "pax:back",
+            Navigation::Category => "pax:category",
+            Navigation::First => "pax:first",
+            Navigation::Forward => "pax:forward",
+            Navigation::Last => "pax:last",
+            Navigation::Member(_) => MEMBER_MENU_ID,
+        }
+    }
+
+    /// Returns the label of this navigation's button.
+    fn label(self) -> &'static str {
+        match self {
+            Navigation::Back => "◀ Back",
+            Navigation::Category => "Category",
+            Navigation::First => "⏮ First",
+            Navigation::Forward => "Next ▶",
+            Navigation::Last => "Last ⏭",
+            Navigation::Member(_) => "Jump to",
+        }
+    }
+
+    /// Returns the navigation for a button or member menu selection, if any.
+    pub(crate) fn from_component(component: &ComponentInteractionData) -> Option<Navigation> {
+        match &component.kind {
+            ComponentInteractionDataKind::StringSelect { values } if component.custom_id == MEMBER_MENU_ID => {
+                values.first()?.parse().ok().map(Navigation::Member)
+            }
+            ComponentInteractionDataKind::Button => {
+                Navigation::BUTTONS.iter().copied().find(|x| x.custom_id() == component.custom_id)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the navigation for a reaction, if any. Only back, forward and members have reactions.
+    pub(crate) fn from_reaction(emoji: &ReactionType) -> Option<Navigation> {
+        match emoji {
+            ReactionType::Unicode(emoji) if *emoji == config().emoji.results_backward => Some(Navigation::Back),
+            ReactionType::Unicode(emoji) if *emoji == config().emoji.results_forward => Some(Navigation::Forward),
+            ReactionType::Unicode(emoji) => MEMBER_REACTIONS.iter().position(|x| x == emoji).map(Navigation::Member),
+            _ => None,
+        }
+    }
+}
+
+/// Defines data that can be rendered to a message.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RenderableMessage {
+    /// Index of the message displaying this result's category in the same response, if any.
+    #[serde(default)]
+    pub category_index: Option<usize>,
+    /// Message content.
+    pub content: String,
+    /// Embed content.
+    pub embed: Option<RenderableEmbed>,
+    /// Whether this message displays a category member that didn't match the query. Member pages are only reached from
+    /// their category page, never by browsing.
+    #[serde(default)]
+    pub member_only: bool,
+    /// Indexes of the messages displaying each member listed on this category page, in list order.
+    #[serde(default)]
+    pub members: Vec<usize>,
+    /// Name of the search result or category displayed, if any.
+    pub result: Option<String>,
+}
+
+/// Contains an entire renderable response that can be navigated through.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RenderableResponse {
+    /// Whether navigation uses buttons. Reactions are used otherwise.
+    #[serde(default)]
+    pub(crate) buttons: bool,
+    /// Currently rendered index.
+    pub(crate) index: usize,
+    /// Vec of [`RenderableMessage`]s.
+    pub(crate) messages: Vec<RenderableMessage>,
+    /// The query this response answers.
+    pub(crate) query: String,
+    /// User who asked for this response, the only user allowed to navigate it unless the guild allows otherwise.
+    #[serde(default)]
+    pub(crate) requester: Option<UserId>,
+    /// Whether messages are posted as plain text, where embeds can't be posted.
+    #[serde(default)]
+    pub(crate) text_only: bool,
+}
+
+impl RenderableResponse {
+    /// Returns every message in this response, in navigation order.
+    pub fn messages(&self) -> &[RenderableMessage] {
+        &self.messages
+    }
+
+    /// Renders every message with `renderer`, in navigation order.
+    pub fn render_with<R: Renderer>(&self, renderer: &R) -> Vec<R::Output> {
+        self.messages.iter().map(|message| renderer.render(message)).collect()
+    }
+
+    /// Edits an existing message, displaying the [`RenderableMessage`] from [`self.messages`] at a specific index in it.
+    pub async fn render(&mut self, index: usize, ctx: &Context, msg: &mut Message) -> Result<(), serenity::Error> {
+        let (content, embed) = if self.text_only {
+            (truncate_content(TextRenderer.render(&self.messages[index])), None)
+        } else {
+            EmbedRenderer.render(&self.messages[index])
+        };
+        let mut edit = EditMessage::new().content(content);
+        if let Some(embed) = embed {
+            edit = edit.embed(embed);
+        }
+        if self.buttons && self.messages.len() > 1 {
+            let buttons = Navigation::BUTTONS
+                .iter()
+                .map(|navigation| {
+                    let target = self.target(index, *navigation);
+                    CreateButton::new(navigation.custom_id())
+                        .style(ButtonStyle::Secondary)
+                        .label(navigation.label())
+                        .disabled(target.is_none() || target == Some(index))
+                })
+                .collect();
+            let mut rows = vec![CreateActionRow::Buttons(buttons)];
+            let members = &self.messages[index].members;
+            if !members.is_empty() {
+                let options = members
+                    .iter()
+                    .enumerate()
+                    .map(|(position, member_index)| {
+                        CreateSelectMenuOption::new(self.member_label(position, *member_index), position.to_string())
+                    })
+                    .collect();
+                let menu = CreateSelectMenu::new(MEMBER_MENU_ID, CreateSelectMenuKind::String { options })
+                    .placeholder("Jump to a member");
+                rows.push(CreateActionRow::SelectMenu(menu));
+            }
+            edit = edit.components(rows);
+        }
+        msg.edit(&ctx.http, edit).await?;
+        self.index = index;
+        Ok(())
+    }
+
+    /// Returns the index a navigation from `from_index` leads to, if any.
+    ///
+    /// Back and forward browse every message but member pages. On a member page, they move between its parts and
+    /// otherwise return to the category page it was picked from.
+    pub fn target(&self, from_index: usize, navigation: Navigation) -> Option<usize> {
+        let last_index = self.messages.iter().rposition(|x| !x.member_only)?;
+        match navigation {
+            Navigation::Back | Navigation::Forward if self.messages.get(from_index)?.member_only => {
+                let current = &self.messages[from_index];
+                let step = match navigation {
+                    Navigation::Back => from_index.checked_sub(1),
+                    _ => Some(from_index + 1),
+                };
+                step.filter(|x| {
+                    self.messages
+                        .get(*x)
+                        .map(|x| x.member_only && x.result == current.result)
+                        .unwrap_or(false)
+                })
+                .or(current.category_index)
+            }
+            Navigation::Back if from_index == 0 => Some(last_index),
+            Navigation::Back => Some(from_index - 1),
+            Navigation::Category => self.messages.get(from_index)?.category_index,
+            Navigation::First => Some(0),
+            Navigation::Forward if from_index >= last_index => Some(0),
+            Navigation::Forward => Some(from_index + 1),
+            Navigation::Last => Some(last_index),
+            Navigation::Member(position) => self.messages.get(from_index)?.members.get(position).copied(),
+        }
+    }
+
+    /// Returns the member menu label for the member at `position` in a list, displayed at `member_index`.
+    fn member_label(&self, position: usize, member_index: usize) -> String {
+        let name = self
+            .messages
+            .get(member_index)
+            .and_then(|x| x.result.as_deref())
+            .unwrap_or_default();
+        format!("{}. {}", position + 1, name)
+            .chars()
+            .take(MEMBER_MENU_LABEL_LIMIT)
+            .collect()
+    }
+
+    /// Returns the number of member reactions needed to select any member of this response.
+    fn member_reaction_count(&self) -> usize {
+        self.messages.iter().map(|x| x.members.len()).max().unwrap_or(0).min(MEMBER_REACTIONS.len())
+    }
+
+    /// Renders the first message into `msg` and stores the response so `requester` can navigate it.
+    ///
+    /// Responses with more than one message get navigation buttons, falling back to reactions where the buttons can't
+    /// be posted. Messages fall back to plain text where embeds can't be posted.
+    pub async fn attach(mut self, ctx: &Context, msg: &mut Message, requester: UserId) -> Result<(), serenity::Error> {
+        self.requester = Some(requester);
+        self.buttons = self.messages.len() > 1;
+        let mut render_result = self.render(0, ctx, msg).await;
+        if let (Err(err), true) = (&render_result, self.buttons) {
+            eprintln!("Failed to add navigation buttons, using reactions instead. {}", err);
+            self.buttons = false;
+            render_result = self.render(0, ctx, msg).await;
+        }
+        if let Err(err) = &render_result {
+            eprintln!("Failed to post an embed, using plain text instead. {}", err);
+            self.text_only = true;
+            self.render(0, ctx, msg).await?;
+        }
+        if self.messages.len() > 1 && !self.buttons {
+            msg.react(&ctx.http, ReactionType::Unicode(config().emoji.results_backward.clone()))
+                .await?;
+            msg.react(&ctx.http, ReactionType::Unicode(config().emoji.results_forward.clone()))
+                .await?;
+            for react in &MEMBER_REACTIONS[..self.member_reaction_count()] {
+                msg.react(&ctx.http, ReactionType::Unicode(react.to_string())).await?;
+            }
+        }
+        let (response_map, store) = {
+            let ctx_data = ctx.data.read().await;
+            (
+                ctx_data
+                    .get::<RenderableResponseKey>()
+                    .expect("Failed to get render response map.")
+                    .clone(),
+                ctx_data.get::<StoreKey>().expect("State store missing.").clone(),
+            )
+        };
+        let save_result = store.lock().await.save_response(msg.channel_id, msg.id, &self);
+        if let Err(err) = save_result {
+            eprintln!("Failed to save a response. {}", err);
+        }
+        let evicted = response_map.lock().await.insert((msg.channel_id, msg.id), self);
+        retire_responses(&ctx.http, &store, evicted).await;
+        Ok(())
+    }
+
+    /// Removes the navigation buttons or reactions from a message this response is no longer cached for.
+    pub async fn detach(&self, http: &Http, channel_id: ChannelId, message_id: MessageId) {
+        if self.messages.len() <= 1 {
+            return;
+        }
+        if self.buttons {
+            let edit_result = channel_id
+                .edit_message(http, message_id, EditMessage::new().components(Vec::new()))
+                .await;
+            if let Err(err) = edit_result {
+                eprintln!("Failed to remove navigation from an expired response. {}", err);
+            }
+            return;
+        }
+        let navigation_reacts = [config().emoji.results_backward.as_str(), config().emoji.results_forward.as_str()];
+        let member_reacts = &MEMBER_REACTIONS[..self.member_reaction_count()];
+        for react in navigation_reacts.iter().chain(member_reacts) {
+            let delete_result = channel_id
+                .delete_reaction(http, message_id, None, ReactionType::Unicode(react.to_string()))
+                .await;
+            if let Err(err) = delete_result {
+                eprintln!("Failed to remove navigation from an expired response. {}", err);
+            }
+        }
+    }
+}
+
+/// Shortens text to fit in a Discord message.
+fn truncate_content(text: String) -> String {
+    if text.chars().count() <= MESSAGE_CONTENT_LIMIT {
+        return text;
+    }
+    let mut truncated = text.chars().take(MESSAGE_CONTENT_LIMIT - 1).collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+pub struct RenderableResponseKey;
+
+/// Cache of navigable responses by the message displaying them.
+pub type RenderableResponseMap = LruCache<(ChannelId, MessageId), RenderableResponse>;
+
+impl TypeMapKey for RenderableResponseKey {
+    type Value = Arc<Mutex<RenderableResponseMap>>;
+}
+
+/// Builds the response cache, sized and expiring as configured in `cache`.
+pub(crate) fn build_response_map() -> RenderableResponseMap {
+    let cache_config = &config().cache;
+    RenderableResponseMap::new(cache_config.size, Duration::from_secs(cache_config.ttl))
+}
+
+/// Removes navigation from responses dropped from the cache, and forgets them.
+async fn retire_responses(
+    http: &Http, store: &Mutex<Store>, retired: Vec<((ChannelId, MessageId), RenderableResponse)>,
+) {
+    for ((channel_id, message_id), render_response) in retired {
+        render_response.detach(http, channel_id, message_id).await;
+        if let Err(err) = store.lock().await.delete_response(channel_id, message_id) {
+            eprintln!("Failed to delete a stored response. {}", err);
+        }
+    }
+}
+
+/// Loads responses saved before the last restart back into the cache, keeping their original expiry.
+pub(crate) async fn restore_responses(response_map: &Mutex<RenderableResponseMap>, store: &Mutex<Store>, http: &Http) {
+    let load_result = store.lock().await.load_responses();
+    let stored_responses = match load_result {
+        Ok(stored_responses) => stored_responses,
+        Err(err) => {
+            eprintln!("Failed to load stored responses. {}", err);
+            return;
+        }
+    };
+    let now = unix_now();
+    let mut retired = Vec::<((ChannelId, MessageId), RenderableResponse)>::new();
+    {
+        let mut response_map = response_map.lock().await;
+        for stored in stored_responses {
+            let age = Duration::from_secs((now - stored.created_at).max(0) as u64);
+            retired.extend(response_map.insert_aged((stored.channel_id, stored.message_id), stored.response, age));
+        }
+        retired.extend(response_map.remove_expired());
+    }
+    retire_responses(http, store, retired).await;
+}
+
+/// Periodically drops expired responses from the cache, removing their navigation reactions.
+pub(crate) fn expire_responses(response_map: Arc<Mutex<RenderableResponseMap>>, store: Arc<Mutex<Store>>, http: Arc<Http>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(RESPONSE_CACHE_SWEEP));
+        loop {
+            interval.tick().await;
+            let expired = response_map.lock().await.remove_expired();
+            retire_responses(&http, &store, expired).await;
+        }
+    });
+}
@@ -0,0 +1,32 @@
+//! This file implements rendering messages as Discord embeds.
+
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+
+use super::{RenderableMessage, Renderer};
+
+/// Renders messages as their content, plus a Discord embed when they have one. This is how responses normally appear
+/// on Discord.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EmbedRenderer;
+
+impl Renderer for EmbedRenderer {
+    /// Message content and embed.
+    type Output = (String, Option<CreateEmbed>);
+
+    fn render(&self, message: &RenderableMessage) -> (String, Option<CreateEmbed>) {
+        let embed = message.embed.as_ref().map(|embed| {
+            let mut e = CreateEmbed::new().title(&embed.title);
+            if let Some(desc) = &embed.description {
+                e = e.description(desc);
+            }
+            if let Some(fields) = embed.fields.clone() {
+                e = e.fields(fields);
+            }
+            if let Some(footer_text) = &embed.footer {
+                e = e.footer(CreateEmbedFooter::new(footer_text));
+            }
+            e
+        });
+        (message.content.clone(), embed)
+    }
+}
@@ -0,0 +1,18 @@
+//! This file implements rendering messages as JSON snapshots.
+
+use serde_json::Value;
+
+use super::{RenderableMessage, Renderer};
+
+/// Renders messages as JSON holding every field of the message, so search output can be compared against saved
+/// snapshots.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonRenderer;
+
+impl Renderer for JsonRenderer {
+    type Output = Value;
+
+    fn render(&self, message: &RenderableMessage) -> Value {
+        serde_json::to_value(message).expect("Renderable messages always serialize.")
+    }
+}
@@ -0,0 +1,333 @@
+//! This file implements splitting messages too large for a single Discord embed into several navigable parts.
+
+use crate::consts::*;
+
+use super::{RenderableEmbed, RenderableMessage};
+
+/// Name of a field continuing the field before it in the same part. Discord rejects empty field names.
+pub(crate) const CONTINUED_FIELD_NAME: &str = "\u{200b}";
+/// Room reserved in each title for its " (part N/M)" label.
+const PART_LABEL_LEN: usize = 16;
+/// Places text may be split, best first. Each is an ending and how many of its bytes stay with the text before it.
+const TEXT_BOUNDARIES: &[&[(&str, usize)]] = &[
+    &[("\n\n", 0)],
+    &[("\n", 0)],
+    &[(". ", 1), ("! ", 1), ("? ", 1)],
+    &[(" ", 0)],
+];
+
+/// A piece of an embed, laid out into parts in order.
+enum EmbedBlock {
+    /// All or part of the description.
+    Description(String),
+    /// All or part of a field. `continued` is set for every piece but the first.
+    Field {
+        continued: bool,
+        inline: bool,
+        name: String,
+        value: String,
+    },
+}
+
+impl RenderableMessage {
+    /// Splits this message into parts that each fit in a Discord embed, titled "(part N/M)". Long text is split on
+    /// paragraph or sentence boundaries where possible. A message that already fits is returned as its only part.
+    pub fn split(&self) -> Vec<RenderableMessage> {
+        let embed = match &self.embed {
+            Some(embed) if needs_split(embed) => embed,
+            _ => return vec![self.clone()],
+        };
+        let mut blocks = Vec::<EmbedBlock>::new();
+        if let Some(desc) = &embed.description {
+            blocks.extend(
+                split_text(desc, EMBED_DESCRIPTION_LIMIT)
+                    .into_iter()
+                    .map(EmbedBlock::Description),
+            );
+        }
+        for (name, value, inline) in embed.fields.iter().flatten() {
+            for (index, chunk) in split_text(value, EMBED_FIELD_VALUE_LIMIT).into_iter().enumerate() {
+                blocks.push(EmbedBlock::Field {
+                    continued: index > 0,
+                    inline: *inline,
+                    name: name.clone(),
+                    value: chunk,
+                });
+            }
+        }
+        let footer_len = embed.footer.as_ref().map(|x| x.chars().count()).unwrap_or(0);
+        let part_limit = EMBED_TOTAL_LIMIT.saturating_sub(embed.title.chars().count() + PART_LABEL_LEN + footer_len);
+        let mut parts = Vec::<RenderableEmbed>::new();
+        let mut part = empty_part(embed);
+        let mut part_len = 0;
+        for block in blocks {
+            match block {
+                EmbedBlock::Description(desc) => {
+                    let desc_len = desc.chars().count();
+                    // Descriptions render above fields, so more description always starts a new part.
+                    if part.description.is_some() || part.fields.is_some() || part_len + desc_len > part_limit {
+                        parts.push(part);
+                        part = empty_part(embed);
+                        part_len = 0;
+                    }
+                    part.description = Some(desc);
+                    part_len += desc_len;
+                }
+                EmbedBlock::Field {
+                    continued,
+                    inline,
+                    name,
+                    value,
+                } => {
+                    let field_count = part.fields.as_ref().map(Vec::len).unwrap_or(0);
+                    let value_len = value.chars().count();
+                    let same_part_name = if continued { CONTINUED_FIELD_NAME } else { name.as_str() };
+                    if field_count > 0
+                        && (field_count >= EMBED_FIELD_COUNT_LIMIT
+                            || part_len + same_part_name.chars().count() + value_len > part_limit)
+                    {
+                        parts.push(part);
+                        part = empty_part(embed);
+                        part_len = 0;
+                    }
+                    let name = match (continued, &part.fields) {
+                        (false, _) => name,
+                        (true, Some(_)) => String::from(CONTINUED_FIELD_NAME),
+                        (true, None) => format!("{} (continued)", name),
+                    };
+                    part_len += name.chars().count() + value_len;
+                    part.fields.get_or_insert_with(Vec::new).push((name, value, inline));
+                }
+            }
+        }
+        parts.push(part);
+        let part_count = parts.len();
+        parts
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut part)| {
+                if part_count > 1 {
+                    part.title = format!("{} (part {}/{})", part.title, index + 1, part_count);
+                }
+                RenderableMessage {
+                    category_index: self.category_index,
+                    content: self.content.clone(),
+                    embed: Some(part),
+                    member_only: self.member_only,
+                    members: self.members.clone(),
+                    result: self.result.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Splits every message that doesn't fit in a Discord embed, keeping each `category_index` and member index pointing at
+/// the first part of the message it links to.
+pub fn split_messages(messages: Vec<RenderableMessage>) -> Vec<RenderableMessage> {
+    let mut first_parts = Vec::<usize>::with_capacity(messages.len());
+    let mut split = Vec::<RenderableMessage>::with_capacity(messages.len());
+    for message in &messages {
+        first_parts.push(split.len());
+        split.extend(message.split());
+    }
+    for message in &mut split {
+        message.category_index = message.category_index.and_then(|index| first_parts.get(index).copied());
+        message.members = message
+            .members
+            .iter()
+            .filter_map(|index| first_parts.get(*index).copied())
+            .collect();
+    }
+    split
+}
+
+/// Returns true if an embed breaks any of Discord's size limits that splitting can fix.
+fn needs_split(embed: &RenderableEmbed) -> bool {
+    let desc_len = embed.description.as_ref().map(|x| x.chars().count()).unwrap_or(0);
+    let fields = embed.fields.as_deref().unwrap_or(&[]);
+    desc_len > EMBED_DESCRIPTION_LIMIT
+        || fields.len() > EMBED_FIELD_COUNT_LIMIT
+        || fields
+            .iter()
+            .any(|(_, value, _)| value.chars().count() > EMBED_FIELD_VALUE_LIMIT)
+        || embed.total_len() > EMBED_TOTAL_LIMIT
+}
+
+/// Returns an embed with the title and footer of `embed`, and no content.
+fn empty_part(embed: &RenderableEmbed) -> RenderableEmbed {
+    RenderableEmbed {
+        description: None,
+        fields: None,
+        footer: embed.footer.clone(),
+        title: embed.title.clone(),
+    }
+}
+
+/// Splits text into chunks of at most `limit` characters. Each chunk ends at the best boundary in [`TEXT_BOUNDARIES`]
+/// found in its second half, or failing that at the last space, so chunks don't end mid-sentence where avoidable.
+fn split_text(text: &str, limit: usize) -> Vec<String> {
+    if text.chars().count() <= limit {
+        return vec![text.to_string()];
+    }
+    let mut chunks = Vec::<String>::new();
+    let mut rest = text.trim();
+    while rest.chars().count() > limit {
+        let window_end = rest.char_indices().nth(limit).map(|(i, _)| i).unwrap_or(rest.len());
+        let window = &rest[..window_end];
+        let min_split = window.char_indices().nth(limit / 2).map(|(i, _)| i).unwrap_or(0);
+        let split_at = TEXT_BOUNDARIES
+            .iter()
+            .find_map(|boundaries| {
+                boundaries
+                    .iter()
+                    .filter_map(|(boundary, keep)| window.rfind(boundary).map(|pos| pos + keep))
+                    .max()
+                    .filter(|pos| *pos > 0 && *pos >= min_split)
+            })
+            .or_else(|| window.rfind(char::is_whitespace).filter(|pos| *pos > 0))
+            .unwrap_or(window_end);
+        chunks.push(rest[..split_at].trim_end().to_string());
+        rest = rest[split_at..].trim_start();
+    }
+    if !rest.is_empty() {
+        chunks.push(rest.to_string());
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(description: Option<String>, fields: Vec<(&str, String)>) -> RenderableMessage {
+        RenderableMessage {
+            category_index: None,
+            content: String::from("Results for: `titan`"),
+            embed: Some(RenderableEmbed {
+                description,
+                fields: Some(
+                    fields
+                        .into_iter()
+                        .map(|(name, value)| (name.to_string(), value, false))
+                        .collect(),
+                ),
+                footer: Some(String::from("Footer")),
+                title: String::from("Titan"),
+            }),
+            member_only: false,
+            members: Vec::new(),
+            result: Some(String::from("Titan")),
+        }
+    }
+
+    fn fields(message: &RenderableMessage) -> Vec<(String, String, bool)> {
+        message
+            .embed
+            .as_ref()
+            .and_then(|x| x.fields.clone())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn text_within_limit_is_unchanged() {
+        assert_eq!(split_text("  Short text.  ", 20), vec!["  Short text.  "]);
+    }
+
+    #[test]
+    fn long_paragraph_splits_between_sentences() {
+        let text = "This sentence is exactly forty chars. ".repeat(40);
+        let chunks = split_text(&text, EMBED_FIELD_VALUE_LIMIT);
+        assert_eq!(chunks.len(), 2);
+        for chunk in &chunks {
+            assert!(chunk.chars().count() <= EMBED_FIELD_VALUE_LIMIT);
+            assert!(chunk.starts_with("This") && chunk.ends_with('.'));
+        }
+    }
+
+    #[test]
+    fn text_without_sentences_splits_between_words() {
+        let text = "word ".repeat(500);
+        let chunks = split_text(&text, EMBED_FIELD_VALUE_LIMIT);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|x| x.chars().count() <= EMBED_FIELD_VALUE_LIMIT));
+        assert!(chunks.iter().all(|x| x.starts_with("word") && x.ends_with("word")));
+        assert_eq!(chunks.join(" "), text.trim());
+    }
+
+    #[test]
+    fn text_without_whitespace_is_cut_at_the_limit() {
+        let text = "a".repeat(2500);
+        let chunks = split_text(&text, EMBED_FIELD_VALUE_LIMIT);
+        let lens = chunks.iter().map(|x| x.len()).collect::<Vec<usize>>();
+        assert_eq!(lens, vec![1024, 1024, 452]);
+        assert_eq!(chunks.concat(), text);
+    }
+
+    #[test]
+    fn multi_byte_characters_are_never_cut() {
+        let text = format!("{}{}", "é".repeat(1000), "🦀".repeat(1000));
+        let chunks = split_text(&text, EMBED_FIELD_VALUE_LIMIT);
+        assert!(chunks.iter().all(|x| x.chars().count() <= EMBED_FIELD_VALUE_LIMIT));
+        assert_eq!(chunks[0].chars().count(), EMBED_FIELD_VALUE_LIMIT);
+        assert_eq!(chunks.concat(), text);
+    }
+
+    #[test]
+    fn message_that_fits_is_not_labelled() {
+        let fitting = message(
+            Some(String::from("Savage Raids")),
+            vec![("Information", String::from("Jumps."))],
+        );
+        assert_eq!(fitting.split(), vec![fitting]);
+    }
+
+    #[test]
+    fn long_field_continues_in_the_same_part() {
+        let parts = message(None, vec![("Information", "word ".repeat(300))]).split();
+        assert_eq!(parts.len(), 1);
+        let part_fields = fields(&parts[0]);
+        assert_eq!(part_fields.len(), 2);
+        assert_eq!(part_fields[0].0, "Information");
+        assert_eq!(part_fields[1].0, CONTINUED_FIELD_NAME);
+        assert_eq!(parts[0].embed.as_ref().unwrap().title, "Titan");
+    }
+
+    #[test]
+    fn oversized_message_is_split_into_labelled_parts() {
+        let parts = message(
+            Some("Description sentence. ".repeat(260)),
+            vec![
+                ("Information", "word ".repeat(1000)),
+                ("External Links", String::from("None")),
+            ],
+        )
+        .split();
+        assert_eq!(parts.len(), 3);
+        for (index, part) in parts.iter().enumerate() {
+            let embed = part.embed.as_ref().unwrap();
+            assert_eq!(embed.title, format!("Titan (part {}/3)", index + 1));
+            assert_eq!(embed.footer.as_deref(), Some("Footer"));
+            assert!(embed.total_len() <= EMBED_TOTAL_LIMIT);
+            assert_eq!(part.result.as_deref(), Some("Titan"));
+        }
+        // The description is never mixed with fields of an earlier part, and a field carried over is labelled.
+        assert!(parts[0].embed.as_ref().unwrap().fields.is_none());
+        assert_eq!(fields(&parts[1])[0].0, "Information");
+        assert_eq!(fields(&parts[2])[0].0, "Information (continued)");
+        assert_eq!(fields(&parts[2]).last().unwrap().0, "External Links");
+    }
+
+    #[test]
+    fn split_messages_points_links_at_first_parts() {
+        let long = message(Some("Description sentence. ".repeat(400)), Vec::new());
+        let mut category = message(Some(String::from("Savage Raids")), Vec::new());
+        category.members = vec![0];
+        let mut result = message(None, Vec::new());
+        result.category_index = Some(1);
+        let split = split_messages(vec![long, category, result]);
+        assert_eq!(split.len(), 5);
+        assert_eq!(split[3].members, vec![0]);
+        assert_eq!(split[4].category_index, Some(3));
+    }
+}
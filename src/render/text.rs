@@ -0,0 +1,42 @@
+//! This file implements rendering messages as plain text with markdown emphasis.
+
+use super::{split::CONTINUED_FIELD_NAME, RenderableMessage, Renderer};
+
+/// Renders messages as markdown text, with the embed title and each field as a bold heading. Used on Discord where
+/// embeds can't be posted, and by paxbot-cli.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TextRenderer;
+
+impl Renderer for TextRenderer {
+    type Output = String;
+
+    fn render(&self, message: &RenderableMessage) -> String {
+        let mut paragraphs = Vec::<String>::new();
+        if !message.content.is_empty() {
+            paragraphs.push(message.content.clone());
+        }
+        if let Some(embed) = &message.embed {
+            match &embed.description {
+                Some(desc) if !desc.trim().is_empty() => {
+                    paragraphs.push(format!("**{}**\n{}", embed.title, desc.trim()))
+                }
+                _ => paragraphs.push(format!("**{}**", embed.title)),
+            }
+            for (name, value, _) in embed.fields.iter().flatten() {
+                if value.trim().is_empty() {
+                    continue;
+                }
+                // Fields continuing the one before them carry on as plain paragraphs.
+                if name == CONTINUED_FIELD_NAME {
+                    paragraphs.push(value.trim().to_string());
+                } else {
+                    paragraphs.push(format!("**{}**\n{}", name, value.trim()));
+                }
+            }
+            if let Some(footer_text) = &embed.footer {
+                paragraphs.push(footer_text.clone());
+            }
+        }
+        paragraphs.join("\n\n")
+    }
+}
@@ -1,10 +1,16 @@
 use rust_fuzzy_search::fuzzy_compare;
 
-use crate::consts::*;
-use crate::{RenderableEmbed, RenderableMessage, RenderableResponse};
+use crate::config::config;
+use crate::consts::CATEGORY_PAGE_MEMBERS;
+use crate::render::{split::split_messages, RenderableEmbed, RenderableMessage, RenderableResponse};
 
 pub mod backend;
-use backend::SearchBackendData;
+use backend::{SearchBackend, SearchBackendCategory, SearchBackendData};
+pub mod index;
+use index::TextScores;
+pub mod query;
+use query::{parse_query, QUERY_SYNTAX_HELP};
+pub mod reload;
 
 /// Best guess from a search.
 #[derive(Clone, Debug, PartialEq)]
@@ -13,6 +19,8 @@ pub enum RenderType {
     Category,
     /// Render a best guess string.
     Guess(Option<String>),
+    /// Render a query that failed to parse, with why.
+    Invalid(String),
     /// Render item results.
     Result,
 }
@@ -30,6 +38,11 @@ pub struct CategoryResult {
     pub text: String,
 }
 
+/// Returns the number of pages a category with `member_count` members is listed on. Empty categories still get a page.
+pub(crate) fn category_pages(member_count: usize) -> usize {
+    member_count.div_ceil(CATEGORY_PAGE_MEMBERS).max(1)
+}
+
 /// Search result struct
 #[derive(Clone, Debug, PartialEq)]
 pub struct SearchResult {
@@ -39,7 +52,7 @@ pub struct SearchResult {
     pub ext_links: Vec<String>,
     /// Primary article name
     pub name: String,
-    /// Relevance score calculated from name and shortname matches.
+    /// Relevance score calculated from name and shortname matches, blended with body text relevance.
     pub score: f32,
     /// Short / abbreviated names
     pub shortname: Vec<String>,
@@ -52,8 +65,9 @@ pub struct SearchResult {
 pub struct SearchResponse {
     /// Category results, sorted by score
     pub category_results: Vec<CategoryResult>,
-    /// Currently rendered result index
-    pub index: usize,
+    /// Members of matched categories that didn't match the query themselves. They are only displayed when picked from
+    /// their category page, and don't count as results.
+    pub member_results: Vec<SearchResult>,
     /// The original query
     pub query: String,
     /// The default render type for this response, based on the best scoring result type.
@@ -63,14 +77,37 @@ pub struct SearchResponse {
 }
 
 impl SearchResponse {
-    /// Gets all renderable messages in sorted order, including appropriate navigation footers.
+    /// Gets a renderable response for this search, with any messages too large for a single embed split into parts.
     pub fn get_renderable_response(&self) -> RenderableResponse {
+        let mut messages = split_messages(self.get_renderable_messages());
+        // Every part counts as a result of its own, so results are numbered again once split.
+        let total_len = messages.iter().filter(|x| !x.member_only).count();
+        for (index, message) in messages.iter_mut().enumerate() {
+            match &mut message.embed {
+                Some(embed) if !message.member_only => embed.footer = Some(result_footer(index, total_len)),
+                _ => (),
+            }
+        }
+        RenderableResponse {
+            buttons: false,
+            index: 0,
+            messages,
+            query: self.query.clone(),
+            requester: None,
+            text_only: false,
+        }
+    }
+
+    /// Gets all renderable messages in sorted order, including appropriate navigation footers. Messages are not yet
+    /// split to fit Discord's embed limits.
+    pub fn get_renderable_messages(&self) -> Vec<RenderableMessage> {
         let mut messages = Vec::<RenderableMessage>::new();
         match &self.render_type {
             RenderType::Category => {
                 // Categories first
-                messages.append(&mut self.categories_to_renderable_messages());
-                messages.append(&mut self.results_to_renderable_messages());
+                messages.append(&mut self.categories_to_renderable_messages(self.category_page_count()));
+                messages.append(&mut self.results_to_renderable_messages(0));
+                messages.append(&mut self.members_to_renderable_messages(0));
                 messages = messages
                     .iter()
                     .cloned()
@@ -87,8 +124,9 @@ impl SearchResponse {
             }
             RenderType::Result => {
                 // Results first
-                messages.append(&mut self.results_to_renderable_messages());
-                messages.append(&mut self.categories_to_renderable_messages());
+                messages.append(&mut self.results_to_renderable_messages(self.results.len()));
+                messages.append(&mut self.categories_to_renderable_messages(0));
+                messages.append(&mut self.members_to_renderable_messages(self.results.len()));
                 messages = messages
                     .iter()
                     .cloned()
@@ -103,151 +141,299 @@ impl SearchResponse {
                     })
                     .collect();
             }
+            RenderType::Invalid(err) => {
+                messages = vec![RenderableMessage {
+                    content: format!("Couldn't read that search: {}.\n{}", err, QUERY_SYNTAX_HELP),
+                    category_index: None,
+                    embed: None,
+                    member_only: false,
+                    members: Vec::new(),
+                    result: None,
+                }]
+            }
             RenderType::Guess(guess_str) => match guess_str {
                 Some(best_guess) => {
                     messages = vec![RenderableMessage {
                         content: format!("No results found. Did you mean `{}`?", best_guess),
+                        category_index: None,
                         embed: None,
+                        member_only: false,
+                        members: Vec::new(),
+                        result: None,
                     }]
                 }
                 None => {
                     messages = vec![RenderableMessage {
                         content: "No results found.".to_string(),
+                        category_index: None,
                         embed: None,
+                        member_only: false,
+                        members: Vec::new(),
+                        result: None,
                     }]
                 }
             },
         }
-        RenderableResponse { index: 0, messages }
+        messages
     }
 
-    /// Returns formatted footer text for the item at a given index.
+    /// Returns formatted footer text for the item at a given index. Member pages, rendered after every result, say
+    /// which category listed them instead of counting towards the results.
     pub fn get_footer_text(&self, for_index: usize) -> String {
-        let total_len = self.category_results.len() + self.results.len();
-        if total_len > 1 {
-            format!("Displaying result {} of {}. Use {} and {} to navigate.\nUse {} if paxbot found what you needed or {} if not.", for_index + 1, total_len, REACT_RESULTS_BACKWARD, REACT_RESULTS_FORWARD, REACT_FEEDBACK_GOOD, REACT_FEEDBACK_BAD)
-        } else {
+        let total_len = self.category_page_count() + self.results.len();
+        let emoji = &config().emoji;
+        let member = for_index
+            .checked_sub(total_len)
+            .and_then(|x| self.member_results.get(x));
+        if let Some(member) = member {
+            let category = member
+                .categories
+                .iter()
+                .find(|category| self.category_results.iter().any(|x| &&x.name == category))
+                .map(String::as_str)
+                .unwrap_or_default();
             format!(
-                "Use {} if paxbot found what you needed or {} if not.",
-                REACT_FEEDBACK_GOOD, REACT_FEEDBACK_BAD
+                "Listed in {}.\nUse {} if paxbot found what you needed or {} if not.",
+                category, emoji.feedback_good, emoji.feedback_bad
             )
+        } else {
+            result_footer(for_index, total_len)
         }
     }
 
-    /// Returns a Vec<RenderableMessage> representing the category results. This does not fill footer text.
-    fn categories_to_renderable_messages(&self) -> Vec<RenderableMessage> {
+    /// Returns a Vec<RenderableMessage> representing the category results, as pages of at most
+    /// [`CATEGORY_PAGE_MEMBERS`] members each. This does not fill footer text.
+    ///
+    /// `result_offset` is the index at which item results will be rendered, used to link each listed member to its
+    /// result in the same response. Members without a result link to their member page, after every result.
+    fn categories_to_renderable_messages(&self, result_offset: usize) -> Vec<RenderableMessage> {
+        let member_offset = self.category_page_count() + self.results.len();
         let mut renderable_categories = Vec::<RenderableMessage>::new();
         for result in &self.category_results {
-            let mut item_list = result
-                .members
-                .iter()
-                .cloned()
-                .take(10)
-                .collect::<Vec<String>>()
-                .join("\n");
-            if result.members.len() > 10 {
-                item_list.push_str(&format!("\n...and {} more.", result.members.len() - 10));
+            let members = self.category_members(result);
+            let page_count = category_pages(members.len());
+            for page in 0..page_count {
+                let page_members = members
+                    .iter()
+                    .skip(page * CATEGORY_PAGE_MEMBERS)
+                    .take(CATEGORY_PAGE_MEMBERS)
+                    .collect::<Vec<&(&str, usize)>>();
+                let item_list = page_members
+                    .iter()
+                    .enumerate()
+                    .map(|(position, (name, _))| format!("{}. {}", position + 1, name))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                let title = if page_count > 1 {
+                    format!("{} (Category, page {}/{})", &result.name, page + 1, page_count)
+                } else {
+                    format!("{} (Category)", &result.name)
+                };
+                renderable_categories.push(RenderableMessage {
+                    category_index: None,
+                    content: format!("Results for: `{}`", self.query),
+                    embed: Some(RenderableEmbed {
+                        // The category text is only repeated on its first page.
+                        description: if page == 0 { Some(result.text.clone()) } else { None },
+                        fields: filled_fields(vec![("Category Members".to_string(), item_list, true)]),
+                        footer: None,
+                        title,
+                    }),
+                    member_only: false,
+                    members: page_members
+                        .iter()
+                        .map(|(_, position)| match position.checked_sub(self.results.len()) {
+                            Some(member_position) => member_offset + member_position,
+                            None => result_offset + position,
+                        })
+                        .collect(),
+                    result: Some(result.name.clone()),
+                });
             }
-            renderable_categories.push(RenderableMessage {
-                content: format!("Results for: `{}`", self.query),
-                embed: Some(RenderableEmbed {
-                    description: Some(result.text.clone()),
-                    fields: Some(vec![("Category Members".to_string(), item_list, true)]),
-                    footer: None,
-                    title: format!("{} (Category)", &result.name),
-                }),
-            });
         }
         renderable_categories
     }
 
+    /// Returns the members of a category that have a result or member page in this response, with their position in
+    /// the results followed by the member results.
+    fn category_members<'a>(&'a self, category: &'a CategoryResult) -> Vec<(&'a str, usize)> {
+        category
+            .members
+            .iter()
+            .filter_map(|name| {
+                self.results
+                    .iter()
+                    .chain(&self.member_results)
+                    .position(|x| &x.name == name)
+                    .map(|position| (name.as_str(), position))
+            })
+            .collect()
+    }
+
+    /// Returns the number of messages all category results render as.
+    fn category_page_count(&self) -> usize {
+        self.category_results
+            .iter()
+            .map(|x| category_pages(self.category_members(x).len()))
+            .sum()
+    }
+
     /// Returns a Vec<RenderableMessage> representing the search item results. This does not fill footer text.
-    fn results_to_renderable_messages(&self) -> Vec<RenderableMessage> {
+    ///
+    /// `category_offset` is the index at which category results will be rendered, used to link each result to its
+    /// first category in the same response.
+    fn results_to_renderable_messages(&self, category_offset: usize) -> Vec<RenderableMessage> {
+        self.items_to_renderable_messages(&self.results, category_offset, false)
+    }
+
+    /// Returns a Vec<RenderableMessage> of member pages, for the members of matched categories that didn't match
+    /// themselves. This does not fill footer text.
+    fn members_to_renderable_messages(&self, category_offset: usize) -> Vec<RenderableMessage> {
+        self.items_to_renderable_messages(&self.member_results, category_offset, true)
+    }
+
+    /// Returns a Vec<RenderableMessage> displaying each of `items`, linked to their first category at
+    /// `category_offset` onwards.
+    fn items_to_renderable_messages(
+        &self, items: &[SearchResult], category_offset: usize, member_only: bool,
+    ) -> Vec<RenderableMessage> {
         let mut renderable_results = Vec::<RenderableMessage>::new();
-        for result in &self.results {
+        for result in items {
             renderable_results.push(RenderableMessage {
+                category_index: result
+                    .categories
+                    .iter()
+                    .find_map(|category| self.category_results.iter().position(|x| &x.name == category))
+                    .map(|position| {
+                        category_offset
+                            + self.category_results[..position]
+                                .iter()
+                                .map(|x| category_pages(self.category_members(x).len()))
+                                .sum::<usize>()
+                    }),
                 content: format!("Results for: `{}`", self.query),
                 embed: Some(RenderableEmbed {
                     description: Some(result.categories.join(", ")),
-                    fields: Some(vec![
+                    fields: filled_fields(vec![
                         ("Information".to_string(), result.text.clone(), false),
                         ("External Links".to_string(), result.ext_links.join("\n"), false),
                     ]),
                     footer: None,
                     title: format!("{} ({})", &result.name, &result.shortname.join(", ")),
                 }),
+                member_only,
+                members: Vec::new(),
+                result: Some(result.name.clone()),
             });
         }
         renderable_results
     }
 }
 
-/// Performs a search on a given [`SearchBackendData`].
-pub async fn search(query: &str, from_data: &SearchBackendData) -> SearchResponse {
+/// Performs a search on the data loaded by a given [`SearchBackend`].
+///
+/// Names and shortnames are fuzzy matched against the query. Body text is scored separately by the backend and
+/// blended in, so body-only matches are still returned, ranked below name matches.
+/// Members of matching categories that didn't match themselves are returned as member results, so every member
+/// listed can be opened, unless the query asks for names or exact phrases.
+///
+/// The query is parsed with [`query::parse_query`] first, and its filters narrow the entries scored. A query that
+/// fails to parse is answered with [`RenderType::Invalid`] rather than searched as literal text.
+pub async fn search(query: &str, from_backend: &dyn SearchBackend) -> SearchResponse {
+    let from_data = from_backend.data();
+    let search_config = &config().search;
     let mut search_response = SearchResponse {
         category_results: Vec::<CategoryResult>::new(),
-        index: 0,
+        member_results: Vec::<SearchResult>::new(),
         query: String::from(query),
         render_type: RenderType::Guess(None),
         results: Vec::<SearchResult>::new(),
     };
+    let parsed_query = match parse_query(query) {
+        Ok(parsed_query) => parsed_query,
+        Err(err) => {
+            search_response.render_type = RenderType::Invalid(err.to_string());
+            return search_response;
+        }
+    };
+    let query_text = parsed_query.text.to_lowercase();
     let mut best_score = 0f32;
+    let text_scores = if parsed_query.name_only || parsed_query.filter_only() {
+        TextScores::default()
+    } else {
+        from_backend.query_text(&parsed_query.text)
+    };
     // Search categories
     for category_item in &from_data.categories {
-        let category_score = fuzzy_compare(&category_item.name.to_lowercase(), &query.to_lowercase());
-        println!("Score: {} -- {} w/ {}", category_score, category_item.name, query); // TODO: Remove
-        if category_score > SEARCH_SCORE_THRESHOLD {
-            search_response.category_results.push(CategoryResult {
-                members: from_data
-                    .search_results
-                    .iter()
-                    .filter(|x| x.categories.contains(&category_item.name))
-                    .map(|x| x.name.clone())
-                    .collect::<Vec<String>>(),
-                name: category_item.name.clone(),
-                score: category_score,
-                text: category_item.text.clone(),
-            });
-            if category_score > best_score {
+        // Filters apply before scoring
+        if !parsed_query.allows_category(category_item) {
+            continue;
+        }
+        let name_score = fuzzy_compare(&category_item.name.to_lowercase(), &query_text);
+        let text_score = text_scores.categories.get(&category_item.name).copied().unwrap_or(0f32);
+        let category_score = if parsed_query.filter_only() {
+            0f32
+        } else if name_score > search_config.score_threshold {
+            if name_score > best_score {
                 search_response.render_type = RenderType::Category;
-                best_score = category_score;
+                best_score = name_score;
             }
-        } else if category_score > best_score && category_score > SEARCH_SUGGEST_THRESHOLD {
-            search_response.render_type = RenderType::Guess(Some(category_item.name.clone()));
-            best_score = category_score;
-        }
+            name_score + search_config.text_blend * text_score
+        } else if text_score > search_config.text_threshold {
+            search_config.text_weight * text_score
+        } else {
+            if name_score > best_score && name_score > search_config.suggest_threshold {
+                search_response.render_type = RenderType::Guess(Some(category_item.name.clone()));
+                best_score = name_score;
+            }
+            continue;
+        };
+        search_response
+            .category_results
+            .push(category_result(category_item, from_data, category_score));
     }
     // Search items
     for search_item in &from_data.search_results {
+        if !parsed_query.allows_item(search_item) {
+            continue;
+        }
         // Get search score
-        let mut item_score = 0f32;
+        let mut name_score = 0f32;
         let mut names = search_item.shortname.iter().map(String::as_str).collect::<Vec<&str>>();
         names.push(search_item.name.as_str());
         for name in names {
-            let name_score = fuzzy_compare(&name.to_lowercase(), &query.to_lowercase());
-            println!("Score: {} -- {} w/ {}", name_score, name, query); // TODO: Remove
-            if name_score > item_score {
-                item_score = name_score;
+            let score = fuzzy_compare(&name.to_lowercase(), &query_text);
+            if score > name_score {
+                name_score = score;
             }
         }
+        let text_score = text_scores.items.get(&search_item.name).copied().unwrap_or(0f32);
         // Push good results
-        if item_score > SEARCH_SCORE_THRESHOLD {
-            search_response.results.push(SearchResult {
-                categories: search_item.categories.clone(),
-                ext_links: search_item.ext_links.clone(),
-                name: search_item.name.clone(),
-                score: item_score,
-                shortname: search_item.shortname.clone(),
-                text: search_item.text.clone(),
-            });
-            if item_score > best_score {
+        let item_score = if parsed_query.filter_only() {
+            0f32
+        } else if name_score > search_config.score_threshold {
+            if name_score > best_score {
                 search_response.render_type = RenderType::Result;
-                best_score = item_score;
+                best_score = name_score;
             }
-        } else if item_score > best_score && item_score > SEARCH_SUGGEST_THRESHOLD {
-            search_response.render_type = RenderType::Guess(Some(search_item.name.clone()));
-            best_score = item_score;
-        }
+            name_score + search_config.text_blend * text_score
+        } else if text_score > search_config.text_threshold {
+            search_config.text_weight * text_score
+        } else {
+            if name_score > best_score && name_score > search_config.suggest_threshold {
+                search_response.render_type = RenderType::Guess(Some(search_item.name.clone()));
+                best_score = name_score;
+            }
+            continue;
+        };
+        search_response.results.push(SearchResult {
+            categories: search_item.categories.clone(),
+            ext_links: search_item.ext_links.clone(),
+            name: search_item.name.clone(),
+            score: item_score,
+            shortname: search_item.shortname.clone(),
+            text: search_item.text.clone(),
+        });
     }
     search_response
         .category_results
@@ -261,6 +447,100 @@ pub async fn search(query: &str, from_data: &SearchBackendData) -> SearchRespons
             Some(score_cmp) => score_cmp,
             None => b.name.cmp(&a.name),
         });
-    println!("{:?}", search_response); // TODO: Remove
+    // Queries made only of filters list everything that passes them, leading with any categories asked for.
+    if parsed_query.filter_only() {
+        search_response.render_type = if !search_response.category_results.is_empty() {
+            RenderType::Category
+        } else if !search_response.results.is_empty() {
+            RenderType::Result
+        } else {
+            RenderType::Guess(None)
+        };
+    }
+    // Body text matches beat a best guess, since they actually contain what was asked for.
+    if let RenderType::Guess(_) = search_response.render_type {
+        let best_category = search_response.category_results.first().map(|x| x.score);
+        let best_result = search_response.results.first().map(|x| x.score);
+        match (best_category, best_result) {
+            (Some(category_score), Some(result_score)) if category_score > result_score => {
+                search_response.render_type = RenderType::Category
+            }
+            (_, Some(_)) => search_response.render_type = RenderType::Result,
+            (Some(_), None) => search_response.render_type = RenderType::Category,
+            (None, None) => (),
+        }
+    }
+    // Members of matching categories get a member page, so category pages can link to all of them. Queries asking for
+    // names or exact phrases only want what matched.
+    if parsed_query.name_only || !parsed_query.phrases.is_empty() {
+        return search_response;
+    }
+    search_response.member_results = from_data
+        .search_results
+        .iter()
+        .filter(|search_item| {
+            parsed_query.allows_item(search_item)
+                && !search_response.results.iter().any(|x| x.name == search_item.name)
+                && search_response
+                    .category_results
+                    .iter()
+                    .any(|x| x.members.contains(&search_item.name))
+        })
+        .map(|search_item| SearchResult {
+            categories: search_item.categories.clone(),
+            ext_links: search_item.ext_links.clone(),
+            name: search_item.name.clone(),
+            score: 0f32,
+            shortname: search_item.shortname.clone(),
+            text: search_item.text.clone(),
+        })
+        .collect::<Vec<SearchResult>>();
     search_response
 }
+
+/// Returns footer text for the result at `for_index` of `total_len`.
+fn result_footer(for_index: usize, total_len: usize) -> String {
+    let emoji = &config().emoji;
+    if total_len > 1 {
+        format!(
+            "Displaying result {} of {}.\nUse {} if paxbot found what you needed or {} if not.",
+            for_index + 1,
+            total_len,
+            emoji.feedback_good,
+            emoji.feedback_bad
+        )
+    } else {
+        format!(
+            "Use {} if paxbot found what you needed or {} if not.",
+            emoji.feedback_good, emoji.feedback_bad
+        )
+    }
+}
+
+/// Drops fields with nothing in them, which Discord rejects, leaving None if no fields are left.
+fn filled_fields(fields: Vec<(String, String, bool)>) -> Option<Vec<(String, String, bool)>> {
+    let filled = fields
+        .into_iter()
+        .filter(|(_, value, _)| !value.trim().is_empty())
+        .collect::<Vec<(String, String, bool)>>();
+    if filled.is_empty() {
+        None
+    } else {
+        Some(filled)
+    }
+}
+
+/// Builds the category result for a category, listing every item in it as a member.
+fn category_result(category_item: &SearchBackendCategory, from_data: &SearchBackendData, score: f32) -> CategoryResult {
+    CategoryResult {
+        members: from_data
+            .search_results
+            .iter()
+            .filter(|x| x.categories.contains(&category_item.name))
+            .map(|x| x.name.clone())
+            .collect::<Vec<String>>(),
+        name: category_item.name.clone(),
+        score,
+        text: category_item.text.clone(),
+    }
+}
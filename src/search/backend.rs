@@ -1,28 +1,123 @@
-//! This file implements the TOML file backend for search data.
-//! This should probably be burned in favor of something less bad eventually.
+//! This file defines the interface for search data backends and the data model they load into.
 
-use std::fs::read_to_string;
+use std::{collections::HashMap, error::Error, fmt, io, path::PathBuf, sync::Arc, time::SystemTime};
 
 use serde::{Deserialize, Serialize};
 use serenity::prelude::*;
-use toml;
+use tokio::sync::RwLock;
+
+use crate::config::{config, ContentBackend};
+
+use super::index::{TextIndex, TextScores};
+
+pub mod file;
+pub mod sqlite;
+use file::FileBackend;
+use sqlite::SqliteBackend;
 
 /// Full possible results fetched from the search backend.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct SearchBackendData {
     /// Vec of all categories
-    #[serde(rename = "category")]
+    #[serde(default, rename = "category")]
     pub categories: Vec<SearchBackendCategory>,
-    #[serde(rename = "search_result")]
+    #[serde(default, rename = "search_result")]
     /// Vec of all search results
     pub search_results: Vec<SearchBackendItem>,
 }
 
+impl SearchBackendData {
+    /// Checks that the data can be served: every entry is named, and no two entries of the same kind share a name.
+    /// All problems found are reported together, one per line.
+    pub fn validate(&self) -> Result<(), SearchBackendError> {
+        let mut problems = Vec::<String>::new();
+        let mut category_names = HashMap::<&str, &Option<SourceLocation>>::new();
+        for category_item in &self.categories {
+            if category_item.name.trim().is_empty() {
+                problems.push(format!(
+                    "A category at {} has an empty name.",
+                    describe_source(&category_item.source)
+                ));
+            } else if let Some(first_source) = category_names.get(category_item.name.as_str()) {
+                problems.push(format!(
+                    "Category {} is defined at {} and again at {}.",
+                    category_item.name,
+                    describe_source(first_source),
+                    describe_source(&category_item.source)
+                ));
+            } else {
+                category_names.insert(&category_item.name, &category_item.source);
+            }
+        }
+        let mut item_names = HashMap::<&str, &Option<SourceLocation>>::new();
+        for search_item in &self.search_results {
+            if search_item.name.trim().is_empty() {
+                problems.push(format!(
+                    "A search result at {} has an empty name.",
+                    describe_source(&search_item.source)
+                ));
+            } else if let Some(first_source) = item_names.get(search_item.name.as_str()) {
+                problems.push(format!(
+                    "Search result {} is defined at {} and again at {}.",
+                    search_item.name,
+                    describe_source(first_source),
+                    describe_source(&search_item.source)
+                ));
+            } else {
+                item_names.insert(&search_item.name, &search_item.source);
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(SearchBackendError::Invalid(problems.join("\n")))
+        }
+    }
+
+    /// Returns a hash of all entries, identifying the loaded content regardless of backend or file layout.
+    ///
+    /// Uses 64 bit FNV-1a, so the hash is stable across builds and platforms.
+    pub fn content_hash(&self) -> u64 {
+        let bytes = serde_json::to_vec(self).unwrap_or_default();
+        bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    /// Returns the number of bytes of text in all entries, roughly the memory the loaded content takes.
+    pub fn text_len(&self) -> usize {
+        let category_len: usize = self.categories.iter().map(|x| x.name.len() + x.text.len()).sum();
+        let item_len: usize = self
+            .search_results
+            .iter()
+            .map(|x| {
+                x.name.len()
+                    + x.text.len()
+                    + x.categories.iter().map(String::len).sum::<usize>()
+                    + x.ext_links.iter().map(String::len).sum::<usize>()
+                    + x.shortname.iter().map(String::len).sum::<usize>()
+            })
+            .sum();
+        category_len + item_len
+    }
+}
+
+/// Formats an optional source location for an error message.
+pub fn describe_source(source: &Option<SourceLocation>) -> String {
+    match source {
+        Some(source) => source.to_string(),
+        None => "an unknown location".to_string(),
+    }
+}
+
 /// Category fetched from the search backend.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct SearchBackendCategory {
     /// Category name
     pub name: String,
+    /// Where the category was defined, if the backend can tell.
+    #[serde(skip)]
+    pub source: Option<SourceLocation>,
     /// Category description
     pub text: String,
 }
@@ -38,27 +133,125 @@ pub struct SearchBackendItem {
     pub name: String,
     /// Short / abbreviated names
     pub shortname: Vec<String>,
+    /// Where the item was defined, if the backend can tell.
+    #[serde(skip)]
+    pub source: Option<SourceLocation>,
     /// Result body text
     pub text: String,
 }
 
-pub struct SearchDataKey;
+/// Location of an entry in a content file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourceLocation {
+    /// 1-based line number of the entry's table header
+    pub line: usize,
+    /// Path to the content file
+    pub path: PathBuf,
+}
 
-/// Reads data from the search backend, returning a [`SearchBackendData`]
-/// containing the possible results.
-pub fn build_search_backend() -> SearchBackendData {
-    let file_data = match read_to_string("./content/content.toml") {
-        Ok(file_data) => file_data,
-        Err(err) => {
-            panic!("Failed to load content.toml: {}", err)
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.line)
+    }
+}
+
+/// Errors raised while loading search data.
+#[derive(Debug)]
+pub enum SearchBackendError {
+    /// A database query failed.
+    Database(PathBuf, rusqlite::Error),
+    /// Content was loaded, but failed validation.
+    Invalid(String),
+    /// Content could not be read from storage.
+    Io(PathBuf, io::Error),
+    /// Content was read, but could not be parsed.
+    Parse(PathBuf, String),
+}
+
+impl fmt::Display for SearchBackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchBackendError::Database(path, err) => write!(f, "Database error in {}: {}", path.display(), err),
+            SearchBackendError::Invalid(err) => write!(f, "Invalid search data: {}", err),
+            SearchBackendError::Io(path, err) => write!(f, "Failed to read {}: {}", path.display(), err),
+            SearchBackendError::Parse(path, err) => write!(f, "Failed to parse {}: {}", path.display(), err),
         }
-    };
-    match toml::from_str::<SearchBackendData>(&file_data) {
-        Ok(toml_data) => toml_data,
-        Err(err) => panic!("Failed to parse content.toml: {}", err),
     }
 }
 
+impl Error for SearchBackendError {}
+
+/// Search data read and validated by [`SearchBackend::prepare`], ready to be swapped in with
+/// [`SearchBackend::replace`].
+pub struct PreparedData {
+    /// Validated entries
+    pub data: SearchBackendData,
+    /// Body text index over `data`, for backends that don't query body text from storage.
+    pub text_index: Option<TextIndex>,
+}
+
+/// A source of search data.
+///
+/// Backends own their currently loaded [`SearchBackendData`] and any indexes needed to query it. A failed reload
+/// must leave the previously loaded data in place.
+pub trait SearchBackend: Send + Sync {
+    /// Reads all entries from storage, without replacing the currently loaded data.
+    fn load(&self) -> Result<SearchBackendData, SearchBackendError>;
+
+    /// Returns the currently loaded data.
+    fn data(&self) -> &SearchBackendData;
+
+    /// Returns when the currently loaded data was loaded.
+    fn loaded_at(&self) -> SystemTime;
+
+    /// Scores the body text of all loaded categories and items against a query.
+    fn query_text(&self, query: &str) -> TextScores;
+
+    /// Reads and validates all entries from storage and builds any indexes needed to query them, without replacing
+    /// the currently loaded data. This does all the slow work of a reload, so it only needs shared access.
+    fn prepare(&self) -> Result<PreparedData, SearchBackendError>;
+
+    /// Replaces the currently loaded data with data from [`SearchBackend::prepare`].
+    fn replace(&mut self, prepared: PreparedData);
+
+    /// Reads and validates all entries from storage, then replaces the currently loaded data with them.
+    fn reload(&mut self) -> Result<(), SearchBackendError> {
+        let prepared = self.prepare()?;
+        self.replace(prepared);
+        Ok(())
+    }
+
+    /// Returns the files or directories that should be watched for content changes.
+    fn watch_paths(&self) -> Vec<PathBuf>;
+}
+
+/// Builds the search backend selected by `content.backend`.
+pub fn build_search_backend() -> Result<Box<dyn SearchBackend>, SearchBackendError> {
+    let content_config = &config().content;
+    match content_config.backend {
+        ContentBackend::Sqlite => Ok(Box::new(SqliteBackend::new(&content_config.sqlite_path)?)),
+        ContentBackend::Toml => Ok(Box::new(FileBackend::new(&content_config.path)?)),
+    }
+}
+
+/// Replaces the contents of the SQLite content store with the TOML content files.
+pub fn import_toml() -> Result<(), SearchBackendError> {
+    let content_config = &config().content;
+    let toml_backend = FileBackend::new(&content_config.path)?;
+    let mut sqlite_backend = SqliteBackend::new(&content_config.sqlite_path)?;
+    sqlite_backend.import(toml_backend.data())?;
+    println!(
+        "Imported {} categories and {} search results from {} into {}.",
+        sqlite_backend.data().categories.len(),
+        sqlite_backend.data().search_results.len(),
+        content_config.path.display(),
+        content_config.sqlite_path.display()
+    );
+    Ok(())
+}
+
+pub struct SearchDataKey;
+
 impl TypeMapKey for SearchDataKey {
-    type Value = SearchBackendData;
+    type Value = Arc<RwLock<Box<dyn SearchBackend>>>;
 }
@@ -0,0 +1,374 @@
+//! This file implements the file backend for search data.
+//! Content can be a single file, or a directory of files that are merged together. TOML files may hold any number of
+//! categories and search results, while markdown files hold a single search result with its metadata in frontmatter.
+
+use std::{
+    fs::{read_dir, read_to_string},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::Deserialize;
+
+use crate::search::index::{TextIndex, TextScores};
+
+use super::{PreparedData, SearchBackend, SearchBackendData, SearchBackendError, SearchBackendItem, SourceLocation};
+
+/// Frontmatter of a markdown article. The article body becomes the search result text.
+#[derive(Clone, Debug, Deserialize)]
+struct ArticleFrontmatter {
+    /// Category membership for item
+    #[serde(default)]
+    categories: Vec<String>,
+    /// External links as strings. Supports markdown [pretty](url) links.
+    #[serde(default)]
+    ext_links: Vec<String>,
+    /// Primary article name
+    name: String,
+    /// Short / abbreviated names
+    #[serde(default)]
+    shortname: Vec<String>,
+}
+
+/// Search backend reading content from a content file, or every content file under a directory.
+pub struct FileBackend {
+    /// Currently loaded data.
+    data: SearchBackendData,
+    /// When [`self.data`] was loaded.
+    loaded_at: SystemTime,
+    /// Path to the content file or directory.
+    path: PathBuf,
+    /// Body text index over [`self.data`].
+    text_index: TextIndex,
+}
+
+impl FileBackend {
+    /// Creates a backend for the content file or directory at `path`, loading it immediately.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<FileBackend, SearchBackendError> {
+        let mut backend = FileBackend {
+            data: SearchBackendData {
+                categories: Vec::new(),
+                search_results: Vec::new(),
+            },
+            loaded_at: SystemTime::now(),
+            path: path.as_ref().to_path_buf(),
+            text_index: TextIndex::default(),
+        };
+        backend.reload()?;
+        Ok(backend)
+    }
+}
+
+/// Reads and merges all content under `path`, which may be a single file or a directory searched recursively for
+/// `*.toml` and `*.md` files. Files are merged in path order. Does not validate.
+pub fn load_path(path: &Path) -> Result<SearchBackendData, SearchBackendError> {
+    if !path.is_dir() {
+        return load_file(path);
+    }
+    let mut search_data = SearchBackendData {
+        categories: Vec::new(),
+        search_results: Vec::new(),
+    };
+    for file_path in content_files(path)? {
+        let mut file_data = load_file(&file_path)?;
+        search_data.categories.append(&mut file_data.categories);
+        search_data.search_results.append(&mut file_data.search_results);
+    }
+    Ok(search_data)
+}
+
+/// Recursively lists content files under a directory, sorted by path.
+fn content_files(dir: &Path) -> Result<Vec<PathBuf>, SearchBackendError> {
+    let mut files = Vec::<PathBuf>::new();
+    let entries = read_dir(dir).map_err(|err| SearchBackendError::Io(dir.to_path_buf(), err))?;
+    for entry in entries {
+        let entry_path = entry
+            .map_err(|err| SearchBackendError::Io(dir.to_path_buf(), err))?
+            .path();
+        if entry_path.is_dir() {
+            files.append(&mut content_files(&entry_path)?);
+        } else if entry_path.extension() == Some("toml".as_ref()) || entry_path.extension() == Some("md".as_ref()) {
+            files.push(entry_path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Reads and parses a single content file, recording where each entry was defined. Does not validate.
+pub fn load_file(path: &Path) -> Result<SearchBackendData, SearchBackendError> {
+    let file_data = read_to_string(path).map_err(|err| SearchBackendError::Io(path.to_path_buf(), err))?;
+    if path.extension() == Some("md".as_ref()) {
+        parse_markdown(path, &file_data)
+    } else {
+        parse_toml(path, &file_data)
+    }
+}
+
+/// Parses a markdown article with YAML (`---`) or TOML (`+++`) frontmatter into a single search result.
+fn parse_markdown(path: &Path, file_data: &str) -> Result<SearchBackendData, SearchBackendError> {
+    let parse_error = |err: String| SearchBackendError::Parse(path.to_path_buf(), err);
+    let lines = file_data.lines().collect::<Vec<&str>>();
+    let delimiter = match lines.first().map(|line| line.trim()) {
+        Some(delimiter @ "---") | Some(delimiter @ "+++") => delimiter,
+        _ => {
+            return Err(parse_error(
+                "Expected frontmatter starting with --- or +++ on the first line.".to_string(),
+            ))
+        }
+    };
+    let close = match lines.iter().skip(1).position(|line| line.trim() == delimiter) {
+        Some(close) => close + 1,
+        None => return Err(parse_error(format!("Frontmatter is never closed with {}.", delimiter))),
+    };
+    let frontmatter = lines[1..close].join("\n");
+    let body = lines[close + 1..].join("\n");
+    let article = match delimiter {
+        "---" => {
+            serde_yaml::from_str::<ArticleFrontmatter>(&frontmatter).map_err(|err| parse_error(err.to_string()))?
+        }
+        _ => toml::from_str::<ArticleFrontmatter>(&frontmatter).map_err(|err| parse_error(err.to_string()))?,
+    };
+    Ok(SearchBackendData {
+        categories: Vec::new(),
+        search_results: vec![SearchBackendItem {
+            categories: article.categories,
+            ext_links: article.ext_links,
+            name: article.name,
+            shortname: article.shortname,
+            source: Some(SourceLocation {
+                line: 1,
+                path: path.to_path_buf(),
+            }),
+            text: body.trim().to_string(),
+        }],
+    })
+}
+
+/// Parses a TOML content file, recording where each entry was defined.
+fn parse_toml(path: &Path, file_data: &str) -> Result<SearchBackendData, SearchBackendError> {
+    let mut search_data = toml::from_str::<SearchBackendData>(file_data)
+        .map_err(|err| SearchBackendError::Parse(path.to_path_buf(), err.to_string()))?;
+    // The TOML parser doesn't keep spans, so match entries to their array table headers in order instead.
+    let mut categories = search_data.categories.iter_mut();
+    let mut search_results = search_data.search_results.iter_mut();
+    for (line_index, line) in file_data.lines().enumerate() {
+        let source = Some(SourceLocation {
+            line: line_index + 1,
+            path: path.to_path_buf(),
+        });
+        match line.split_whitespace().collect::<String>().as_str() {
+            "[[category]]" => {
+                if let Some(category_item) = categories.next() {
+                    category_item.source = source;
+                }
+            }
+            "[[search_result]]" => {
+                if let Some(search_item) = search_results.next() {
+                    search_item.source = source;
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(search_data)
+}
+
+impl SearchBackend for FileBackend {
+    fn load(&self) -> Result<SearchBackendData, SearchBackendError> {
+        load_path(&self.path)
+    }
+
+    fn data(&self) -> &SearchBackendData {
+        &self.data
+    }
+
+    fn loaded_at(&self) -> SystemTime {
+        self.loaded_at
+    }
+
+    fn query_text(&self, query: &str) -> TextScores {
+        self.text_index.search(query)
+    }
+
+    fn prepare(&self) -> Result<PreparedData, SearchBackendError> {
+        let data = self.load()?;
+        data.validate()?;
+        Ok(PreparedData {
+            text_index: Some(TextIndex::build(&data)),
+            data,
+        })
+    }
+
+    fn replace(&mut self, prepared: PreparedData) {
+        let PreparedData { data, text_index } = prepared;
+        self.text_index = text_index.unwrap_or_else(|| TextIndex::build(&data));
+        self.data = data;
+        self.loaded_at = SystemTime::now();
+    }
+
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(result: Result<SearchBackendData, SearchBackendError>) -> String {
+        match result {
+            Err(SearchBackendError::Parse(path, err)) => {
+                assert_eq!(path, Path::new("titan.md"));
+                err
+            }
+            Err(err) => panic!("Expected a parse error, got {}.", err),
+            Ok(_) => panic!("Expected a parse error."),
+        }
+    }
+
+    #[test]
+    fn parses_yaml_frontmatter() {
+        let file_data = concat!(
+            "---\n",
+            "name: Titan\n",
+            "shortname: [T1]\n",
+            "categories: [Savage Raids]\n",
+            "ext_links:\n",
+            "  - \"[Guide](https://example.com/titan)\"\n",
+            "---\n",
+            "\n",
+            "Titan jumps three times.\n",
+            "\n",
+            "Spread out.\n",
+        );
+        let search_data = parse_markdown(Path::new("titan.md"), file_data).unwrap();
+        assert!(search_data.categories.is_empty());
+        let titan = &search_data.search_results[0];
+        assert_eq!(titan.name, "Titan");
+        assert_eq!(titan.shortname, vec!["T1"]);
+        assert_eq!(titan.categories, vec!["Savage Raids"]);
+        assert_eq!(titan.ext_links, vec!["[Guide](https://example.com/titan)"]);
+        assert_eq!(titan.text, "Titan jumps three times.\n\nSpread out.");
+        assert_eq!(titan.source.as_ref().map(|x| x.line), Some(1));
+    }
+
+    #[test]
+    fn parses_toml_frontmatter() {
+        let file_data = "+++\nname = \"Titan\"\ncategories = [\"Savage Raids\"]\n+++\nTitan jumps three times.\n";
+        let search_data = parse_markdown(Path::new("titan.md"), file_data).unwrap();
+        let titan = &search_data.search_results[0];
+        assert_eq!(titan.name, "Titan");
+        assert!(titan.shortname.is_empty());
+        assert_eq!(titan.categories, vec!["Savage Raids"]);
+        assert_eq!(titan.text, "Titan jumps three times.");
+    }
+
+    #[test]
+    fn frontmatter_closes_with_its_opening_delimiter() {
+        let file_data = "+++\nname = \"Titan\"\n+++\nA --- rule.\n---\nStill body text.";
+        let search_data = parse_markdown(Path::new("titan.md"), file_data).unwrap();
+        assert_eq!(search_data.search_results[0].text, "A --- rule.\n---\nStill body text.");
+    }
+
+    #[test]
+    fn rejects_missing_frontmatter() {
+        let err = parse_error(parse_markdown(Path::new("titan.md"), "name: Titan\n---\nTitan jumps."));
+        assert_eq!(err, "Expected frontmatter starting with --- or +++ on the first line.");
+        let err = parse_error(parse_markdown(Path::new("titan.md"), ""));
+        assert_eq!(err, "Expected frontmatter starting with --- or +++ on the first line.");
+    }
+
+    #[test]
+    fn rejects_unclosed_frontmatter() {
+        let err = parse_error(parse_markdown(
+            Path::new("titan.md"),
+            "---\nname: Titan\n+++\nTitan jumps.",
+        ));
+        assert_eq!(err, "Frontmatter is never closed with ---.");
+    }
+
+    #[test]
+    fn rejects_frontmatter_without_a_name() {
+        parse_error(parse_markdown(
+            Path::new("titan.md"),
+            "---\nshortname: [T1]\n---\nTitan jumps.",
+        ));
+    }
+
+    #[test]
+    fn parses_crlf_markdown() {
+        let file_data = concat!(
+            "---\r\n",
+            "name: Titan\r\n",
+            "shortname: [T1]\r\n",
+            "---\r\n",
+            "Titan jumps three times.\r\n",
+            "\r\n",
+            "Spread out.\r\n",
+        );
+        let search_data = parse_markdown(Path::new("titan.md"), file_data).unwrap();
+        let titan = &search_data.search_results[0];
+        assert_eq!(titan.name, "Titan");
+        assert_eq!(titan.shortname, vec!["T1"]);
+        assert_eq!(titan.text, "Titan jumps three times.\n\nSpread out.");
+    }
+
+    #[test]
+    fn records_toml_entry_lines() {
+        let file_data = concat!(
+            "# Raids\n",
+            "[[category]]\n",
+            "name = \"Savage Raids\"\n",
+            "text = \"Harder raids.\"\n",
+            "\n",
+            "[[search_result]]\n",
+            "categories = [\"Savage Raids\"]\n",
+            "ext_links = []\n",
+            "name = \"Titan\"\n",
+            "shortname = [\"T1\"]\n",
+            "text = \"Titan jumps.\"\n",
+            "\n",
+            "[[ search_result ]]\n",
+            "categories = [\"Savage Raids\"]\n",
+            "ext_links = []\n",
+            "name = \"Garuda\"\n",
+            "shortname = []\n",
+            "text = \"Stand behind a pillar.\"\n",
+        );
+        let search_data = parse_toml(Path::new("content.toml"), file_data).unwrap();
+        let lines = |x: &Option<SourceLocation>| x.as_ref().map(|x| (x.path.clone(), x.line));
+        assert_eq!(
+            lines(&search_data.categories[0].source),
+            Some((PathBuf::from("content.toml"), 2))
+        );
+        assert_eq!(
+            lines(&search_data.search_results[0].source),
+            Some((PathBuf::from("content.toml"), 6))
+        );
+        assert_eq!(
+            lines(&search_data.search_results[1].source),
+            Some((PathBuf::from("content.toml"), 13))
+        );
+    }
+
+    #[test]
+    fn records_crlf_toml_entry_lines() {
+        let file_data = concat!(
+            "[[category]]\r\n",
+            "name = \"Savage Raids\"\r\n",
+            "text = \"Harder raids.\"\r\n",
+            "\r\n",
+            "[[search_result]]\r\n",
+            "categories = [\"Savage Raids\"]\r\n",
+            "ext_links = []\r\n",
+            "name = \"Titan\"\r\n",
+            "shortname = []\r\n",
+            "text = \"Titan jumps.\"\r\n",
+        );
+        let search_data = parse_toml(Path::new("content.toml"), file_data).unwrap();
+        assert_eq!(search_data.categories[0].source.as_ref().map(|x| x.line), Some(1));
+        assert_eq!(search_data.search_results[0].source.as_ref().map(|x| x.line), Some(5));
+        assert_eq!(search_data.search_results[0].text, "Titan jumps.");
+    }
+}
@@ -0,0 +1,305 @@
+//! This file implements the SQLite backend for search data.
+//! Items and categories are stored in their own tables, with body text mirrored into an FTS5 table by triggers.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use rusqlite::{params, Connection};
+
+use crate::search::index::{normalize_score, words, TextScores};
+
+use super::{
+    PreparedData, SearchBackend, SearchBackendCategory, SearchBackendData, SearchBackendError, SearchBackendItem,
+};
+
+/// Schema for the content database. Safe to run against an existing database.
+const SCHEMA: &str = r#"
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE IF NOT EXISTS category (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    text TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS item (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    text TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS item_shortname (
+    item_id INTEGER NOT NULL REFERENCES item(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    shortname TEXT NOT NULL,
+    PRIMARY KEY (item_id, position)
+);
+
+CREATE TABLE IF NOT EXISTS item_ext_link (
+    item_id INTEGER NOT NULL REFERENCES item(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    link TEXT NOT NULL,
+    PRIMARY KEY (item_id, position)
+);
+
+CREATE TABLE IF NOT EXISTS item_category (
+    item_id INTEGER NOT NULL REFERENCES item(id) ON DELETE CASCADE,
+    category_id INTEGER NOT NULL REFERENCES category(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (item_id, category_id)
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS content_fts USING fts5(
+    kind UNINDEXED,
+    name UNINDEXED,
+    text,
+    tokenize = 'porter unicode61'
+);
+
+CREATE TRIGGER IF NOT EXISTS category_fts_insert AFTER INSERT ON category BEGIN
+    INSERT INTO content_fts (kind, name, text) VALUES ('category', new.name, new.text);
+END;
+CREATE TRIGGER IF NOT EXISTS category_fts_update AFTER UPDATE ON category BEGIN
+    UPDATE content_fts SET name = new.name, text = new.text WHERE kind = 'category' AND name = old.name;
+END;
+CREATE TRIGGER IF NOT EXISTS category_fts_delete AFTER DELETE ON category BEGIN
+    DELETE FROM content_fts WHERE kind = 'category' AND name = old.name;
+END;
+
+CREATE TRIGGER IF NOT EXISTS item_fts_insert AFTER INSERT ON item BEGIN
+    INSERT INTO content_fts (kind, name, text) VALUES ('item', new.name, new.text);
+END;
+CREATE TRIGGER IF NOT EXISTS item_fts_update AFTER UPDATE ON item BEGIN
+    UPDATE content_fts SET name = new.name, text = new.text WHERE kind = 'item' AND name = old.name;
+END;
+CREATE TRIGGER IF NOT EXISTS item_fts_delete AFTER DELETE ON item BEGIN
+    DELETE FROM content_fts WHERE kind = 'item' AND name = old.name;
+END;
+"#;
+
+/// Search backend reading content from a local SQLite database.
+pub struct SqliteBackend {
+    /// Open database connection.
+    conn: Mutex<Connection>,
+    /// Currently loaded data.
+    data: SearchBackendData,
+    /// When [`self.data`] was loaded.
+    loaded_at: SystemTime,
+    /// Path to the database file.
+    path: PathBuf,
+}
+
+impl SqliteBackend {
+    /// Opens (creating if needed) the content database at `path`, loading it immediately.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<SqliteBackend, SearchBackendError> {
+        let path = path.as_ref().to_path_buf();
+        let conn = Connection::open(&path).map_err(|err| SearchBackendError::Database(path.clone(), err))?;
+        conn.execute_batch(SCHEMA)
+            .map_err(|err| SearchBackendError::Database(path.clone(), err))?;
+        let mut backend = SqliteBackend {
+            conn: Mutex::new(conn),
+            data: SearchBackendData {
+                categories: Vec::new(),
+                search_results: Vec::new(),
+            },
+            loaded_at: SystemTime::now(),
+            path,
+        };
+        backend.reload()?;
+        Ok(backend)
+    }
+
+    /// Replaces the entire contents of the database with `from_data`, then reloads.
+    ///
+    /// This is the one-shot migration path from the TOML content file. Items may only reference categories that
+    /// exist in `from_data`.
+    pub fn import(&mut self, from_data: &SearchBackendData) -> Result<(), SearchBackendError> {
+        {
+            let mut conn = self.conn.lock().expect("SQLite connection lock poisoned.");
+            let tx = conn.transaction().map_err(|err| self.db_error(err))?;
+            tx.execute_batch("DELETE FROM item; DELETE FROM category;")
+                .map_err(|err| self.db_error(err))?;
+            let mut category_ids = HashMap::<&str, i64>::new();
+            for category_item in &from_data.categories {
+                tx.execute(
+                    "INSERT INTO category (name, text) VALUES (?1, ?2)",
+                    params![category_item.name, category_item.text],
+                )
+                .map_err(|err| self.db_error(err))?;
+                category_ids.insert(&category_item.name, tx.last_insert_rowid());
+            }
+            for search_item in &from_data.search_results {
+                tx.execute(
+                    "INSERT INTO item (name, text) VALUES (?1, ?2)",
+                    params![search_item.name, search_item.text],
+                )
+                .map_err(|err| self.db_error(err))?;
+                let item_id = tx.last_insert_rowid();
+                for (position, shortname) in search_item.shortname.iter().enumerate() {
+                    tx.execute(
+                        "INSERT INTO item_shortname (item_id, position, shortname) VALUES (?1, ?2, ?3)",
+                        params![item_id, position as i64, shortname],
+                    )
+                    .map_err(|err| self.db_error(err))?;
+                }
+                for (position, link) in search_item.ext_links.iter().enumerate() {
+                    tx.execute(
+                        "INSERT INTO item_ext_link (item_id, position, link) VALUES (?1, ?2, ?3)",
+                        params![item_id, position as i64, link],
+                    )
+                    .map_err(|err| self.db_error(err))?;
+                }
+                for (position, category) in search_item.categories.iter().enumerate() {
+                    let category_id = match category_ids.get(category.as_str()) {
+                        Some(category_id) => *category_id,
+                        None => {
+                            return Err(SearchBackendError::Invalid(format!(
+                                "Search result {} references undefined category {}.",
+                                search_item.name, category
+                            )))
+                        }
+                    };
+                    tx.execute(
+                        "INSERT INTO item_category (item_id, category_id, position) VALUES (?1, ?2, ?3)",
+                        params![item_id, category_id, position as i64],
+                    )
+                    .map_err(|err| self.db_error(err))?;
+                }
+            }
+            tx.commit().map_err(|err| self.db_error(err))?;
+        }
+        self.reload()
+    }
+
+    /// Wraps a database error with this backend's path.
+    fn db_error(&self, err: rusqlite::Error) -> SearchBackendError {
+        SearchBackendError::Database(self.path.clone(), err)
+    }
+
+    /// Reads all entries using an open connection.
+    fn load_from(&self, conn: &Connection) -> rusqlite::Result<SearchBackendData> {
+        let mut categories = Vec::<SearchBackendCategory>::new();
+        let mut stmt = conn.prepare("SELECT name, text FROM category ORDER BY id")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            categories.push(SearchBackendCategory {
+                name: row.get(0)?,
+                source: None,
+                text: row.get(1)?,
+            });
+        }
+        let mut search_results = Vec::<SearchBackendItem>::new();
+        let mut item_ids = HashMap::<i64, usize>::new();
+        let mut stmt = conn.prepare("SELECT id, name, text FROM item ORDER BY id")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            item_ids.insert(row.get(0)?, search_results.len());
+            search_results.push(SearchBackendItem {
+                categories: Vec::new(),
+                ext_links: Vec::new(),
+                name: row.get(1)?,
+                shortname: Vec::new(),
+                source: None,
+                text: row.get(2)?,
+            });
+        }
+        let mut stmt = conn.prepare("SELECT item_id, shortname FROM item_shortname ORDER BY item_id, position")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            if let Some(index) = item_ids.get(&row.get(0)?) {
+                search_results[*index].shortname.push(row.get(1)?);
+            }
+        }
+        let mut stmt = conn.prepare("SELECT item_id, link FROM item_ext_link ORDER BY item_id, position")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            if let Some(index) = item_ids.get(&row.get(0)?) {
+                search_results[*index].ext_links.push(row.get(1)?);
+            }
+        }
+        let mut stmt = conn.prepare(
+            "SELECT item_category.item_id, category.name FROM item_category \
+             JOIN category ON category.id = item_category.category_id \
+             ORDER BY item_category.item_id, item_category.position",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            if let Some(index) = item_ids.get(&row.get(0)?) {
+                search_results[*index].categories.push(row.get(1)?);
+            }
+        }
+        Ok(SearchBackendData {
+            categories,
+            search_results,
+        })
+    }
+}
+
+impl SearchBackend for SqliteBackend {
+    fn load(&self) -> Result<SearchBackendData, SearchBackendError> {
+        let conn = self.conn.lock().expect("SQLite connection lock poisoned.");
+        self.load_from(&conn).map_err(|err| self.db_error(err))
+    }
+
+    fn data(&self) -> &SearchBackendData {
+        &self.data
+    }
+
+    fn loaded_at(&self) -> SystemTime {
+        self.loaded_at
+    }
+
+    fn query_text(&self, query: &str) -> TextScores {
+        let mut text_scores = TextScores::default();
+        // Quote every word so FTS5 query syntax in user input is treated as plain text.
+        let match_expr = words(query)
+            .iter()
+            .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+            .collect::<Vec<String>>()
+            .join(" OR ");
+        if match_expr.is_empty() {
+            return text_scores;
+        }
+        let conn = self.conn.lock().expect("SQLite connection lock poisoned.");
+        let result = conn
+            .prepare("SELECT kind, name, bm25(content_fts) FROM content_fts WHERE content_fts MATCH ?1")
+            .and_then(|mut stmt| {
+                let mut rows = stmt.query(params![match_expr])?;
+                while let Some(row) = rows.next()? {
+                    let kind: String = row.get(0)?;
+                    let name: String = row.get(1)?;
+                    // FTS5 reports bm25 as a negative number, lower being more relevant.
+                    let score = normalize_score(-row.get::<_, f64>(2)? as f32);
+                    match kind.as_str() {
+                        "category" => text_scores.categories.insert(name, score),
+                        _ => text_scores.items.insert(name, score),
+                    };
+                }
+                Ok(())
+            });
+        if let Err(err) = result {
+            eprintln!("Failed to query {}: {}", self.path.display(), err);
+        }
+        text_scores
+    }
+
+    fn prepare(&self) -> Result<PreparedData, SearchBackendError> {
+        let data = self.load()?;
+        data.validate()?;
+        // Body text is queried through FTS5, so no in-memory index is needed.
+        Ok(PreparedData { data, text_index: None })
+    }
+
+    fn replace(&mut self, prepared: PreparedData) {
+        self.data = prepared.data;
+        self.loaded_at = SystemTime::now();
+    }
+
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
+}
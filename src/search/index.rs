@@ -0,0 +1,238 @@
+//! This file implements an in-memory inverted index over article and category body text.
+//! Documents are ranked with BM25 and the raw scores are squashed into the 0..1 range so they can be blended with
+//! fuzzy name scores.
+
+use std::collections::HashMap;
+
+use super::backend::SearchBackendData;
+
+/// BM25 term frequency saturation parameter.
+const BM25_K1: f32 = 1.2;
+/// BM25 document length normalization parameter.
+const BM25_B: f32 = 0.75;
+/// Raw BM25 score at which a normalized text score reaches 0.5.
+const BM25_SATURATION: f32 = 1.0;
+
+/// Words that carry no meaning for a search and are never indexed.
+const STOP_WORDS: &[&str] = &[
+    "a", "about", "an", "and", "are", "as", "at", "be", "but", "by", "can", "do", "does", "for", "from", "get", "how",
+    "i", "if", "in", "into", "is", "it", "its", "me", "my", "of", "on", "or", "so", "that", "the", "their", "them",
+    "there", "this", "to", "was", "what", "when", "where", "which", "who", "why", "will", "with", "you", "your",
+];
+
+/// A document stored in the index.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum IndexedDoc {
+    /// Category description, by category name.
+    Category(String),
+    /// Search item body text, by item name.
+    Item(String),
+}
+
+/// Normalized (0..1) text relevance scores for a query, keyed by name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextScores {
+    /// Scores for category descriptions
+    pub categories: HashMap<String, f32>,
+    /// Scores for search item body text
+    pub items: HashMap<String, f32>,
+}
+
+/// Inverted index over the body text of a [`SearchBackendData`].
+#[derive(Clone, Debug, Default)]
+pub struct TextIndex {
+    /// Average document length, in terms.
+    avg_doc_len: f32,
+    /// Document length, in terms, for each indexed document.
+    doc_lens: Vec<usize>,
+    /// Indexed documents, addressed by position.
+    docs: Vec<IndexedDoc>,
+    /// Map of term to (document index, term frequency) postings.
+    postings: HashMap<String, Vec<(usize, u32)>>,
+}
+
+impl TextIndex {
+    /// Builds an index from all category descriptions and search item texts in a [`SearchBackendData`].
+    pub fn build(from_data: &SearchBackendData) -> TextIndex {
+        let mut index = TextIndex::default();
+        for category_item in &from_data.categories {
+            index.add(IndexedDoc::Category(category_item.name.clone()), &category_item.text);
+        }
+        for search_item in &from_data.search_results {
+            index.add(IndexedDoc::Item(search_item.name.clone()), &search_item.text);
+        }
+        let total_len: usize = index.doc_lens.iter().sum();
+        if !index.docs.is_empty() {
+            index.avg_doc_len = total_len as f32 / index.docs.len() as f32;
+        }
+        index
+    }
+
+    /// Scores every document containing at least one query term, returning normalized scores.
+    pub fn search(&self, query: &str) -> TextScores {
+        let mut raw_scores = HashMap::<usize, f32>::new();
+        let doc_count = self.docs.len() as f32;
+        let mut query_terms = tokenize(query);
+        query_terms.sort();
+        query_terms.dedup();
+        for term in query_terms {
+            let postings = match self.postings.get(&term) {
+                Some(postings) => postings,
+                None => continue,
+            };
+            let doc_freq = postings.len() as f32;
+            let idf = (1f32 + (doc_count - doc_freq + 0.5) / (doc_freq + 0.5)).ln();
+            for (doc, term_freq) in postings {
+                let term_freq = *term_freq as f32;
+                let len_norm = 1f32 - BM25_B + BM25_B * self.doc_lens[*doc] as f32 / self.avg_doc_len;
+                *raw_scores.entry(*doc).or_insert(0f32) +=
+                    idf * term_freq * (BM25_K1 + 1f32) / (term_freq + BM25_K1 * len_norm);
+            }
+        }
+        let mut text_scores = TextScores::default();
+        for (doc, raw_score) in raw_scores {
+            let score = normalize_score(raw_score);
+            match &self.docs[doc] {
+                IndexedDoc::Category(name) => text_scores.categories.insert(name.clone(), score),
+                IndexedDoc::Item(name) => text_scores.items.insert(name.clone(), score),
+            };
+        }
+        text_scores
+    }
+
+    /// Adds a single document to the index. Does not update the average document length.
+    fn add(&mut self, doc: IndexedDoc, text: &str) {
+        let doc_index = self.docs.len();
+        let terms = tokenize(text);
+        self.doc_lens.push(terms.len());
+        self.docs.push(doc);
+        let mut term_freqs = HashMap::<String, u32>::new();
+        for term in terms {
+            *term_freqs.entry(term).or_insert(0) += 1;
+        }
+        for (term, term_freq) in term_freqs {
+            self.postings.entry(term).or_default().push((doc_index, term_freq));
+        }
+    }
+}
+
+/// Splits text into lowercase, stemmed terms, dropping stop words.
+pub fn tokenize(text: &str) -> Vec<String> {
+    words(text).iter().map(|word| stem(word)).collect()
+}
+
+/// Splits text into lowercase words, dropping stop words.
+pub fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+        .collect()
+}
+
+/// Squashes a raw BM25 score into the 0..1 range.
+pub fn normalize_score(raw_score: f32) -> f32 {
+    raw_score / (raw_score + BM25_SATURATION)
+}
+
+/// Strips a few common English suffixes so that e.g. "unlocks", "unlocked" and "unlocking" share a term.
+/// "es" is only stripped after a sibilant, as in "boxes", so "zones" still shares a term with "zone".
+fn stem(word: &str) -> String {
+    for suffix in &["ing", "ed", "es", "s"] {
+        if let Some(stem) = word.strip_suffix(suffix) {
+            let sibilant = ["s", "x", "z", "ch", "sh"].iter().any(|x| stem.ends_with(x));
+            let allowed = match *suffix {
+                "es" => sibilant,
+                "s" => !stem.ends_with('s'),
+                _ => true,
+            };
+            if stem.chars().count() >= 3 && allowed {
+                return stem.to_string();
+            }
+        }
+    }
+    word.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::backend::{SearchBackendCategory, SearchBackendItem};
+
+    fn item(name: &str, text: &str) -> SearchBackendItem {
+        SearchBackendItem {
+            categories: Vec::new(),
+            ext_links: Vec::new(),
+            name: name.to_string(),
+            shortname: Vec::new(),
+            source: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn stem_strips_plurals() {
+        assert_eq!(stem("zones"), stem("zone"));
+        assert_eq!(stem("games"), "game");
+        assert_eq!(stem("unlocks"), "unlock");
+        assert_eq!(stem("boxes"), "box");
+        assert_eq!(stem("wishes"), "wish");
+        assert_eq!(stem("passes"), "pass");
+    }
+
+    #[test]
+    fn stem_strips_verb_endings() {
+        assert_eq!(stem("unlocked"), "unlock");
+        assert_eq!(stem("unlocking"), "unlock");
+        assert_eq!(stem("passed"), "pass");
+    }
+
+    #[test]
+    fn stem_keeps_short_and_double_s_words() {
+        assert_eq!(stem("class"), "class");
+        assert_eq!(stem("gas"), "gas");
+        assert_eq!(stem("bed"), "bed");
+        assert_eq!(stem("uses"), "use");
+    }
+
+    #[test]
+    fn tokenize_drops_stop_words() {
+        assert_eq!(tokenize("Where is the Zones' entrance?"), vec!["zone", "entrance"]);
+    }
+
+    #[test]
+    fn search_ranks_by_term_frequency_and_rarity() {
+        let index = TextIndex::build(&SearchBackendData {
+            categories: vec![SearchBackendCategory {
+                name: String::from("Raids"),
+                source: None,
+                text: String::from("Raids are fought by groups of eight."),
+            }],
+            search_results: vec![
+                item(
+                    "Titan",
+                    "Titan jumps twice. Spread out before each jump, then stack after the jumps.",
+                ),
+                item("Garuda", "Garuda jumps once near the end of the fight."),
+                item("Ifrit", "Ifrit only breathes fire."),
+            ],
+        });
+        let scores = index.search("jump fire");
+        assert!(scores.items["Titan"] > scores.items["Garuda"]);
+        // "fire" is in fewer documents than "jump", so a single mention of it outweighs a single mention of "jump".
+        assert!(scores.items["Ifrit"] > scores.items["Garuda"]);
+        assert!(!scores.categories.contains_key("Raids"));
+        assert!(scores.items.values().all(|x| *x > 0f32 && *x < 1f32));
+    }
+
+    #[test]
+    fn search_matches_plural_forms() {
+        let index = TextIndex::build(&SearchBackendData {
+            categories: Vec::new(),
+            search_results: vec![item("Zone Map", "Lists every zone."), item("Other", "Nothing here.")],
+        });
+        let scores = index.search("zones");
+        assert!(scores.items.contains_key("Zone Map"));
+        assert!(!scores.items.contains_key("Other"));
+    }
+}
@@ -0,0 +1,292 @@
+//! This file implements the search query language: free text, quoted exact phrases, `cat:` and `-cat:` category
+//! filters, and `name:` to match names only. Values containing spaces are quoted, e.g. `cat:"Savage Raids" titan`.
+
+use std::{error::Error, fmt};
+
+use super::backend::{SearchBackendCategory, SearchBackendItem};
+
+/// Query syntax summary, shown along with parse errors.
+pub const QUERY_SYNTAX_HELP: &str = "`cat:\"Category\"` only searches a category (repeat it to search several), \
+                                     `-cat:\"Category\"` leaves one out, `\"exact phrase\"` requires a phrase and \
+                                     `name:text` only matches names.";
+
+/// Errors raised while parsing a query.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum QueryError {
+    /// The query has no text or `cat:` filters, e.g. only `-cat:` filters, leaving nothing to search for.
+    NothingToSearch,
+    /// A filter was given without a value, e.g. `cat:` on its own.
+    MissingValue(&'static str),
+    /// A quote was opened but never closed.
+    UnclosedQuote,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::NothingToSearch => write!(f, "there's nothing to search for"),
+            QueryError::MissingValue(filter) => write!(f, "`{}` needs something after it", filter),
+            QueryError::UnclosedQuote => write!(f, "a quote is opened but never closed"),
+        }
+    }
+}
+
+impl Error for QueryError {}
+
+/// A parsed search query.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Query {
+    /// Categories searched, from `cat:` filters. Results must belong to at least one of them.
+    pub categories: Vec<String>,
+    /// Categories results must not belong to, from `-cat:` filters.
+    pub excluded_categories: Vec<String>,
+    /// Whether only names and shortnames are matched, set by any `name:` term.
+    pub name_only: bool,
+    /// Exact phrases results must contain, from quoted text.
+    pub phrases: Vec<String>,
+    /// Text results are scored against: free words, `name:` terms and phrases, in query order.
+    pub text: String,
+}
+
+impl Query {
+    /// Returns true if a category passes this query's filters. `cat:` filters only allow the categories they name.
+    pub fn allows_category(&self, category: &SearchBackendCategory) -> bool {
+        let names = [category.name.as_str()];
+        self.includes_any(std::slice::from_ref(&category.name))
+            && !self.excludes_category(&category.name)
+            && self.contains_phrases(&names, &category.text)
+    }
+
+    /// Returns true if a category is left out by a `-cat:` filter.
+    pub fn excludes_category(&self, name: &str) -> bool {
+        self.excluded_categories.iter().any(|x| x.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns true if an item passes this query's filters. Items in any category named by a `cat:` filter pass it.
+    pub fn allows_item(&self, item: &SearchBackendItem) -> bool {
+        let names = std::iter::once(&item.name)
+            .chain(item.shortname.iter())
+            .map(String::as_str)
+            .collect::<Vec<&str>>();
+        self.includes_any(&item.categories)
+            && !item.categories.iter().any(|x| self.excludes_category(x))
+            && self.contains_phrases(&names, &item.text)
+    }
+
+    /// Returns true if there are no `cat:` filters, or one of `categories` is named by one.
+    fn includes_any(&self, categories: &[String]) -> bool {
+        self.categories.is_empty()
+            || categories
+                .iter()
+                .any(|category| self.categories.iter().any(|x| x.eq_ignore_ascii_case(category)))
+    }
+
+    /// Returns true if there is nothing to score results against, so every result passing the filters matches.
+    pub fn filter_only(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Returns true if every phrase appears in one of `names`, or in `text` unless matching names only.
+    fn contains_phrases(&self, names: &[&str], text: &str) -> bool {
+        let names = names.iter().map(|x| x.to_lowercase()).collect::<Vec<String>>();
+        let text = text.to_lowercase();
+        self.phrases.iter().all(|phrase| {
+            let phrase = phrase.to_lowercase();
+            names.iter().any(|x| x.contains(&phrase)) || (!self.name_only && text.contains(&phrase))
+        })
+    }
+}
+
+/// Parses a query. Words that aren't filters are searched as text, so ordinary searches are unaffected.
+pub fn parse_query(query: &str) -> Result<Query, QueryError> {
+    let mut parsed = Query::default();
+    let mut text_terms = Vec::<String>::new();
+    let mut rest = query.trim_start();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let (phrase, after) = take_quoted(quoted)?;
+            if !phrase.trim().is_empty() {
+                parsed.phrases.push(phrase.trim().to_string());
+                text_terms.push(phrase.trim().to_string());
+            }
+            rest = after.trim_start();
+            continue;
+        }
+        let (term, after) = match filter_prefix(rest) {
+            Some(filter) => {
+                let (value, after) = take_value(&rest[filter.len()..])?;
+                if value.trim().is_empty() {
+                    return Err(QueryError::MissingValue(filter));
+                }
+                match filter {
+                    "cat:" => parsed.categories.push(value.trim().to_string()),
+                    "-cat:" => parsed.excluded_categories.push(value.trim().to_string()),
+                    _ => {
+                        parsed.name_only = true;
+                        text_terms.push(value.trim().to_string());
+                    }
+                }
+                (None, after)
+            }
+            None => {
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                (Some(&rest[..end]), &rest[end..])
+            }
+        };
+        if let Some(term) = term {
+            text_terms.push(term.to_string());
+        }
+        rest = after.trim_start();
+    }
+    parsed.text = text_terms.join(" ");
+    if parsed.text.is_empty() && parsed.categories.is_empty() {
+        return Err(QueryError::NothingToSearch);
+    }
+    Ok(parsed)
+}
+
+/// Returns the filter `text` starts with, if any. Filters are matched case insensitively.
+fn filter_prefix(text: &str) -> Option<&'static str> {
+    ["cat:", "-cat:", "name:"].iter().copied().find(|filter| {
+        text.get(..filter.len())
+            .map(|x| x.eq_ignore_ascii_case(filter))
+            .unwrap_or(false)
+    })
+}
+
+/// Splits a filter value, quoted or up to the next whitespace, from the text after it.
+fn take_value(text: &str) -> Result<(&str, &str), QueryError> {
+    match text.strip_prefix('"') {
+        Some(quoted) => take_quoted(quoted),
+        None => {
+            let end = text.find(char::is_whitespace).unwrap_or(text.len());
+            Ok((&text[..end], &text[end..]))
+        }
+    }
+}
+
+/// Splits quoted text, starting just after its opening quote, from the text after its closing quote.
+fn take_quoted(text: &str) -> Result<(&str, &str), QueryError> {
+    let end = text.find('"').ok_or(QueryError::UnclosedQuote)?;
+    Ok((&text[..end], &text[end + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, categories: &[&str]) -> SearchBackendItem {
+        SearchBackendItem {
+            categories: categories.iter().map(|x| x.to_string()).collect(),
+            ext_links: Vec::new(),
+            name: name.to_string(),
+            shortname: Vec::new(),
+            source: None,
+            text: String::from("Spread out before each landing."),
+        }
+    }
+
+    fn category(name: &str) -> SearchBackendCategory {
+        SearchBackendCategory {
+            name: name.to_string(),
+            source: None,
+            text: String::new(),
+        }
+    }
+
+    #[test]
+    fn parses_quoted_category_and_text() {
+        let query = parse_query("cat:\"Savage Raids\" titan").unwrap();
+        assert_eq!(query.categories, vec!["Savage Raids"]);
+        assert_eq!(query.text, "titan");
+        assert!(!query.name_only);
+        assert!(query.phrases.is_empty());
+    }
+
+    #[test]
+    fn parses_quoted_filter_values() {
+        let query = parse_query("-cat:\"Legacy Raids\" cat:\"Savage Raids\"").unwrap();
+        assert_eq!(query.categories, vec!["Savage Raids"]);
+        assert_eq!(query.excluded_categories, vec!["Legacy Raids"]);
+        assert!(query.filter_only());
+    }
+
+    #[test]
+    fn parses_name_with_free_words() {
+        let query = parse_query("name:titan jumps").unwrap();
+        assert!(query.name_only);
+        assert_eq!(query.text, "titan jumps");
+    }
+
+    #[test]
+    fn parses_phrases_as_text() {
+        let query = parse_query("titan \"spread out\"").unwrap();
+        assert_eq!(query.phrases, vec!["spread out"]);
+        assert_eq!(query.text, "titan spread out");
+    }
+
+    #[test]
+    fn filters_are_case_insensitive() {
+        let query = parse_query("CAT:Raids Name:titan -Cat:Legacy").unwrap();
+        assert_eq!(query.categories, vec!["Raids"]);
+        assert_eq!(query.excluded_categories, vec!["Legacy"]);
+        assert!(query.name_only);
+        assert_eq!(query.text, "titan");
+    }
+
+    #[test]
+    fn plain_words_containing_colons_are_text() {
+        let query = parse_query("phase:2 titan").unwrap();
+        assert!(query.categories.is_empty());
+        assert_eq!(query.text, "phase:2 titan");
+    }
+
+    #[test]
+    fn rejects_excluded_category_alone() {
+        assert_eq!(parse_query("-cat:Legacy"), Err(QueryError::NothingToSearch));
+        assert_eq!(parse_query("   "), Err(QueryError::NothingToSearch));
+        assert_eq!(parse_query("\"\""), Err(QueryError::NothingToSearch));
+    }
+
+    #[test]
+    fn rejects_filters_without_values() {
+        assert_eq!(parse_query("cat:"), Err(QueryError::MissingValue("cat:")));
+        assert_eq!(
+            parse_query("titan -cat: garuda"),
+            Err(QueryError::MissingValue("-cat:"))
+        );
+        assert_eq!(parse_query("name:\"\" titan"), Err(QueryError::MissingValue("name:")));
+    }
+
+    #[test]
+    fn rejects_unclosed_quotes() {
+        assert_eq!(parse_query("titan \"spread out"), Err(QueryError::UnclosedQuote));
+        assert_eq!(parse_query("cat:\"Savage Raids titan"), Err(QueryError::UnclosedQuote));
+    }
+
+    #[test]
+    fn multiple_categories_allow_any_of_them() {
+        let query = parse_query("cat:Savage cat:Extreme").unwrap();
+        assert!(query.allows_category(&category("savage")));
+        assert!(query.allows_category(&category("Extreme")));
+        assert!(!query.allows_category(&category("Legacy")));
+        assert!(query.allows_item(&item("Titan", &["Savage"])));
+        assert!(query.allows_item(&item("Ifrit", &["Extreme", "Legacy"])));
+        assert!(!query.allows_item(&item("Garuda", &["Legacy"])));
+    }
+
+    #[test]
+    fn excluded_categories_win() {
+        let query = parse_query("cat:Savage -cat:Legacy titan").unwrap();
+        assert!(!query.allows_category(&category("Legacy")));
+        assert!(!query.allows_item(&item("Titan", &["Savage", "legacy"])));
+    }
+
+    #[test]
+    fn phrases_must_match() {
+        let query = parse_query("\"spread out\"").unwrap();
+        assert!(query.allows_item(&item("Titan", &[])));
+        let query = parse_query("name:titan \"spread out\"").unwrap();
+        assert!(!query.allows_item(&item("Titan", &[])));
+    }
+}
@@ -0,0 +1,104 @@
+//! This file implements reloading search data while the bot is running, whenever content changes on disk.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{mpsc::channel, Arc},
+    thread,
+    time::Duration,
+};
+
+use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+use serenity::{http::Http, model::id::UserId};
+use tokio::{runtime::Handle, sync::RwLock};
+
+use super::backend::SearchBackend;
+
+/// How long content must stay unchanged before a reload is triggered.
+const WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Watches the content paths of a search backend on a background thread, reloading it whenever they change.
+///
+/// A failed reload keeps the previously loaded data and is reported to the bot owners by direct message.
+pub async fn watch_search_backend(
+    search_backend: Arc<RwLock<Box<dyn SearchBackend>>>, http: Arc<Http>, owners: HashSet<UserId>,
+) {
+    let watch_paths = search_backend
+        .read()
+        .await
+        .watch_paths()
+        .iter()
+        .filter_map(|path| match path.canonicalize() {
+            Ok(path) => Some(path),
+            Err(err) => {
+                eprintln!("Not watching {} for changes: {}", path.display(), err);
+                None
+            }
+        })
+        .collect::<Vec<PathBuf>>();
+    let runtime = Handle::current();
+    thread::spawn(move || {
+        let (tx, rx) = channel();
+        let mut content_watcher = match watcher(tx, WATCH_DEBOUNCE) {
+            Ok(content_watcher) => content_watcher,
+            Err(err) => {
+                eprintln!("Failed to start content watcher: {}", err);
+                return;
+            }
+        };
+        for path in &watch_paths {
+            // Editors often replace files rather than writing them in place, so watch the parent of single files.
+            let watch_result = match path.parent() {
+                Some(parent) if path.is_file() => content_watcher.watch(parent, RecursiveMode::NonRecursive),
+                _ => content_watcher.watch(path, RecursiveMode::Recursive),
+            };
+            if let Err(err) = watch_result {
+                eprintln!("Failed to watch {} for changes: {}", path.display(), err);
+            }
+        }
+        while let Ok(event) = rx.recv() {
+            let changed_path = match event {
+                DebouncedEvent::Create(path) | DebouncedEvent::Write(path) | DebouncedEvent::Rename(_, path) => path,
+                // A watched file being removed is usually an editor replacing it, which is followed by a create.
+                DebouncedEvent::Remove(path) if !watch_paths.contains(&path) => path,
+                _ => continue,
+            };
+            if !is_watched(&changed_path, &watch_paths) {
+                continue;
+            }
+            runtime.block_on(async {
+                // Only swapping the new data in needs the write lock, so searches aren't held up by a slow reload.
+                let prepare_result = search_backend.read().await.prepare();
+                match prepare_result {
+                    Ok(prepared) => {
+                        search_backend.write().await.replace(prepared);
+                        println!("Reloaded search data after {} changed.", changed_path.display());
+                    }
+                    Err(why) => {
+                        eprintln!("Failed to reload search data: {}", why);
+                        let report = format!(
+                            "Content changed on disk, but could not be reloaded. Keeping previously loaded data.\n```text\n{}\n```",
+                            why
+                        );
+                        for owner in &owners {
+                            let dm_result = match owner.create_dm_channel(&http).await {
+                                Ok(dm_channel) => dm_channel.say(&http, &report).await.map(|_| ()),
+                                Err(err) => Err(err),
+                            };
+                            if let Err(err) = dm_result {
+                                eprintln!("Failed to report a reload failure to {}: {}", owner, err);
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Returns true if `path` is one of the watched files, or inside one of the watched directories.
+fn is_watched(path: &Path, watch_paths: &[PathBuf]) -> bool {
+    watch_paths
+        .iter()
+        .any(|watch_path| path == watch_path || (watch_path.is_dir() && path.starts_with(watch_path)))
+}
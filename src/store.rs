@@ -0,0 +1,109 @@
+//! This file implements paxbot's local state database, for bot data that must survive restarts.
+//! Each kind of data keeps its queries in its own submodule.
+
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::Connection;
+use serenity::prelude::*;
+use tokio::sync::Mutex;
+
+pub mod channel;
+pub mod feedback;
+pub mod guild_setting;
+pub mod report;
+pub mod response;
+pub mod search_log;
+pub mod stats;
+
+/// Schema for the state database. Safe to run against an existing database.
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS allowed_channel (
+    guild_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, channel_id)
+);
+
+CREATE TABLE IF NOT EXISTS command_count (
+    command TEXT PRIMARY KEY,
+    runs INTEGER NOT NULL DEFAULT 0,
+    failures INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS feedback (
+    channel_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    positive INTEGER NOT NULL,
+    guild_id INTEGER,
+    query TEXT NOT NULL,
+    result TEXT,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (channel_id, message_id, user_id, positive)
+);
+
+CREATE TABLE IF NOT EXISTS search_log (
+    id INTEGER PRIMARY KEY,
+    channel_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    guild_id INTEGER,
+    query TEXT NOT NULL,
+    render_type TEXT NOT NULL,
+    guess TEXT,
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS search_log_render_type ON search_log (render_type, guild_id);
+
+CREATE TABLE IF NOT EXISTS guild_setting (
+    guild_id INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (guild_id, key)
+);
+
+CREATE TABLE IF NOT EXISTS response (
+    channel_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    data TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (channel_id, message_id)
+);
+
+CREATE TABLE IF NOT EXISTS served_user (
+    user_id INTEGER PRIMARY KEY,
+    first_seen INTEGER NOT NULL
+);
+"#;
+
+/// Handle to the state database.
+pub struct Store {
+    /// Open database connection.
+    conn: Connection,
+}
+
+impl Store {
+    /// Opens (creating if needed) the state database at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> rusqlite::Result<Store> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Store { conn })
+    }
+}
+
+/// Returns the current time as seconds since the unix epoch.
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+pub struct StoreKey;
+
+impl TypeMapKey for StoreKey {
+    type Value = Arc<Mutex<Store>>;
+}
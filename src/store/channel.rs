@@ -0,0 +1,51 @@
+//! This file implements storage for the per-guild allowlist of channels paxbot answers searches in.
+
+use rusqlite::params;
+use serenity::model::id::{ChannelId, GuildId};
+
+use super::Store;
+
+impl Store {
+    /// Adds a channel to a guild's allowlist. Returns false if it was already allowed.
+    pub fn allow_channel(&self, guild_id: GuildId, channel_id: ChannelId) -> rusqlite::Result<bool> {
+        let inserted = self.conn.execute(
+            "INSERT OR IGNORE INTO allowed_channel (guild_id, channel_id) VALUES (?1, ?2)",
+            params![guild_id.get() as i64, channel_id.get() as i64],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    /// Removes a channel from a guild's allowlist. Returns false if it wasn't allowed.
+    pub fn disallow_channel(&self, guild_id: GuildId, channel_id: ChannelId) -> rusqlite::Result<bool> {
+        let deleted = self.conn.execute(
+            "DELETE FROM allowed_channel WHERE guild_id = ?1 AND channel_id = ?2",
+            params![guild_id.get() as i64, channel_id.get() as i64],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    /// Returns a guild's allowlist. An empty allowlist allows every channel.
+    pub fn allowed_channels(&self, guild_id: GuildId) -> rusqlite::Result<Vec<ChannelId>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT channel_id FROM allowed_channel WHERE guild_id = ?1 ORDER BY channel_id")?;
+        let mut rows = stmt.query(params![guild_id.get() as i64])?;
+        let mut channels = Vec::<ChannelId>::new();
+        while let Some(row) = rows.next()? {
+            channels.push(ChannelId::new(row.get::<_, i64>(0)? as u64));
+        }
+        Ok(channels)
+    }
+
+    /// Returns whether searches are answered in a channel, which is true for every channel if the guild has no
+    /// allowlist.
+    pub fn channel_allowed(&self, guild_id: GuildId, channel_id: ChannelId) -> rusqlite::Result<bool> {
+        let allowed = self.conn.query_row(
+            "SELECT NOT EXISTS (SELECT 1 FROM allowed_channel WHERE guild_id = ?1) \
+             OR EXISTS (SELECT 1 FROM allowed_channel WHERE guild_id = ?1 AND channel_id = ?2)",
+            params![guild_id.get() as i64, channel_id.get() as i64],
+            |row| row.get(0),
+        )?;
+        Ok(allowed)
+    }
+}
@@ -0,0 +1,58 @@
+//! This file implements storage for feedback reactions on search responses.
+
+use rusqlite::params;
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+
+use super::{unix_now, Store};
+
+/// A single user's feedback on a search response.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Feedback {
+    /// Channel of the response message
+    pub channel_id: ChannelId,
+    /// Guild of the response message, if not a DM
+    pub guild_id: Option<GuildId>,
+    /// Response message
+    pub message_id: MessageId,
+    /// Whether paxbot found what the user needed
+    pub positive: bool,
+    /// The original query
+    pub query: String,
+    /// Name of the result or category shown when feedback was given, if any
+    pub result: Option<String>,
+    /// User giving feedback
+    pub user_id: UserId,
+}
+
+impl Store {
+    /// Records feedback, replacing any identical feedback from the same user on the same message.
+    pub fn record_feedback(&self, feedback: &Feedback) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO feedback \
+             (channel_id, message_id, user_id, positive, guild_id, query, result, created_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                feedback.channel_id.get() as i64,
+                feedback.message_id.get() as i64,
+                feedback.user_id.get() as i64,
+                feedback.positive,
+                feedback.guild_id.map(|x| x.get() as i64),
+                feedback.query,
+                feedback.result,
+                unix_now()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Retracts a user's feedback on a message.
+    pub fn retract_feedback(
+        &self, channel_id: ChannelId, message_id: MessageId, user_id: UserId, positive: bool,
+    ) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "DELETE FROM feedback WHERE channel_id = ?1 AND message_id = ?2 AND user_id = ?3 AND positive = ?4",
+            params![channel_id.get() as i64, message_id.get() as i64, user_id.get() as i64, positive],
+        )?;
+        Ok(())
+    }
+}
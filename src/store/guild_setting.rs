@@ -0,0 +1,67 @@
+//! This file implements storage for per-guild settings, kept as key/value pairs so new settings need no migration.
+
+use rusqlite::{params, OptionalExtension};
+use serenity::model::id::GuildId;
+
+use super::Store;
+
+/// Setting key allowing anyone to navigate any response in a guild.
+const OPEN_NAVIGATION: &str = "open_navigation";
+/// Setting key for a guild's command prefix.
+const PREFIX: &str = "prefix";
+
+impl Store {
+    /// Returns the raw value of a guild setting, if it was ever set.
+    pub fn guild_setting(&self, guild_id: GuildId, key: &str) -> rusqlite::Result<Option<String>> {
+        self.conn
+            .query_row(
+                "SELECT value FROM guild_setting WHERE guild_id = ?1 AND key = ?2",
+                params![guild_id.get() as i64, key],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    /// Sets the raw value of a guild setting.
+    pub fn set_guild_setting(&self, guild_id: GuildId, key: &str, value: &str) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO guild_setting (guild_id, key, value) VALUES (?1, ?2, ?3)",
+            params![guild_id.get() as i64, key, value],
+        )?;
+        Ok(())
+    }
+
+    /// Removes a guild setting, going back to its default.
+    pub fn clear_guild_setting(&self, guild_id: GuildId, key: &str) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "DELETE FROM guild_setting WHERE guild_id = ?1 AND key = ?2",
+            params![guild_id.get() as i64, key],
+        )?;
+        Ok(())
+    }
+
+    /// Returns whether anyone may navigate responses in a guild, rather than only the user who searched.
+    pub fn open_navigation(&self, guild_id: GuildId) -> rusqlite::Result<bool> {
+        Ok(self.guild_setting(guild_id, OPEN_NAVIGATION)?.as_deref() == Some("true"))
+    }
+
+    /// Sets whether anyone may navigate responses in a guild.
+    pub fn set_open_navigation(&self, guild_id: GuildId, open: bool) -> rusqlite::Result<()> {
+        self.set_guild_setting(guild_id, OPEN_NAVIGATION, if open { "true" } else { "false" })
+    }
+
+    /// Returns the command prefix a guild has set, if any.
+    pub fn prefix(&self, guild_id: GuildId) -> rusqlite::Result<Option<String>> {
+        self.guild_setting(guild_id, PREFIX)
+    }
+
+    /// Sets the command prefix for a guild.
+    pub fn set_prefix(&self, guild_id: GuildId, prefix: &str) -> rusqlite::Result<()> {
+        self.set_guild_setting(guild_id, PREFIX, prefix)
+    }
+
+    /// Removes a guild's command prefix, going back to the configured prefix.
+    pub fn clear_prefix(&self, guild_id: GuildId) -> rusqlite::Result<()> {
+        self.clear_guild_setting(guild_id, PREFIX)
+    }
+}
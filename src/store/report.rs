@@ -0,0 +1,109 @@
+//! This file implements the maintainer report built from stored feedback and searches.
+
+use rusqlite::{params, Row};
+use serenity::model::id::GuildId;
+
+use super::Store;
+
+/// Seconds after a best guess during which searching for the guess counts as taking it.
+const GUESS_TAKEN_WINDOW: i64 = 600;
+
+/// A result that received negative feedback.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DislikedResult {
+    /// Negative feedback count
+    pub bad: u64,
+    /// Positive feedback count
+    pub good: u64,
+    /// Result or category name
+    pub result: String,
+}
+
+/// A query that got a best guess, which the user did not search for afterwards.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IgnoredGuess {
+    /// Number of times the guess was ignored
+    pub count: u64,
+    /// The suggested name
+    pub guess: String,
+    /// The original query, lowercased
+    pub query: String,
+}
+
+/// A query that found nothing at all.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MissedQuery {
+    /// Number of times the query was made
+    pub count: u64,
+    /// The original query, lowercased
+    pub query: String,
+}
+
+/// Feedback and content gap report for maintainers.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Report {
+    /// Results with negative feedback, most disliked first
+    pub disliked: Vec<DislikedResult>,
+    /// Queries whose best guess was ignored, most frequent first
+    pub ignored_guesses: Vec<IgnoredGuess>,
+    /// Queries with no results, most frequent first
+    pub missed: Vec<MissedQuery>,
+}
+
+impl Store {
+    /// Builds a report of at most `limit` rows per section, limited to one guild or covering all of them.
+    pub fn report(&self, guild_id: Option<GuildId>, limit: usize) -> rusqlite::Result<Report> {
+        let guild_id = guild_id.map(|x| x.get() as i64);
+        let limit = limit as i64;
+        let mut report = Report::default();
+        let mut stmt = self.conn.prepare(
+            "SELECT result, SUM(NOT positive) AS bad, SUM(positive) AS good FROM feedback \
+             WHERE result IS NOT NULL AND (?1 IS NULL OR guild_id = ?1) \
+             GROUP BY result HAVING bad > 0 ORDER BY bad DESC, good ASC, result LIMIT ?2",
+        )?;
+        let mut rows = stmt.query(params![guild_id, limit])?;
+        while let Some(row) = rows.next()? {
+            report.disliked.push(DislikedResult {
+                bad: get_count(row, 1)?,
+                good: get_count(row, 2)?,
+                result: row.get(0)?,
+            });
+        }
+        let mut stmt = self.conn.prepare(
+            "SELECT lower(query) AS lower_query, COUNT(*) AS times FROM search_log \
+             WHERE render_type = 'none' AND (?1 IS NULL OR guild_id = ?1) \
+             GROUP BY lower_query ORDER BY times DESC, lower_query LIMIT ?2",
+        )?;
+        let mut rows = stmt.query(params![guild_id, limit])?;
+        while let Some(row) = rows.next()? {
+            report.missed.push(MissedQuery {
+                count: get_count(row, 1)?,
+                query: row.get(0)?,
+            });
+        }
+        let mut stmt = self.conn.prepare(
+            "SELECT lower(query) AS lower_query, guess, COUNT(*) AS times FROM search_log AS guessed \
+             WHERE render_type = 'guess' AND (?1 IS NULL OR guild_id = ?1) AND NOT EXISTS ( \
+                 SELECT 1 FROM search_log AS taken \
+                 WHERE taken.user_id = guessed.user_id AND taken.channel_id = guessed.channel_id \
+                 AND lower(taken.query) = lower(guessed.guess) \
+                 AND taken.created_at BETWEEN guessed.created_at AND guessed.created_at + ?3 \
+             ) \
+             GROUP BY lower_query, guess ORDER BY times DESC, lower_query LIMIT ?2",
+        )?;
+        let mut rows = stmt.query(params![guild_id, limit, GUESS_TAKEN_WINDOW])?;
+        while let Some(row) = rows.next()? {
+            report.ignored_guesses.push(IgnoredGuess {
+                count: get_count(row, 2)?,
+                guess: row.get(1)?,
+                query: row.get(0)?,
+            });
+        }
+        Ok(report)
+    }
+}
+
+/// Reads a non-negative count column.
+fn get_count(row: &Row, index: usize) -> rusqlite::Result<u64> {
+    Ok(row.get::<_, i64>(index)?.max(0) as u64)
+}
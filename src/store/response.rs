@@ -0,0 +1,82 @@
+//! This file implements storage for navigable responses, so navigation keeps working across restarts.
+
+use rusqlite::params;
+use serenity::model::id::{ChannelId, MessageId};
+
+use super::{unix_now, Store};
+use crate::render::RenderableResponse;
+
+/// A navigable response loaded from the state database.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StoredResponse {
+    /// Channel of the response message
+    pub channel_id: ChannelId,
+    /// When the response was first saved, in seconds since the unix epoch
+    pub created_at: i64,
+    /// Response message
+    pub message_id: MessageId,
+    /// The response itself
+    pub response: RenderableResponse,
+}
+
+impl Store {
+    /// Saves a response, keeping the original creation time if it was saved before.
+    pub fn save_response(
+        &self, channel_id: ChannelId, message_id: MessageId, response: &RenderableResponse,
+    ) -> rusqlite::Result<()> {
+        let data =
+            serde_json::to_string(response).map_err(|err| rusqlite::Error::ToSqlConversionFailure(Box::new(err)))?;
+        self.conn.execute(
+            "INSERT INTO response (channel_id, message_id, data, created_at) VALUES (?1, ?2, ?3, ?4) \
+             ON CONFLICT (channel_id, message_id) DO UPDATE SET data = excluded.data",
+            params![channel_id.get() as i64, message_id.get() as i64, data, unix_now()],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes a saved response.
+    pub fn delete_response(&self, channel_id: ChannelId, message_id: MessageId) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "DELETE FROM response WHERE channel_id = ?1 AND message_id = ?2",
+            params![channel_id.get() as i64, message_id.get() as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Loads every saved response, oldest first. Responses that can no longer be read are deleted.
+    pub fn load_responses(&self) -> rusqlite::Result<Vec<StoredResponse>> {
+        let mut stored_responses = Vec::<StoredResponse>::new();
+        let mut unreadable = Vec::<(i64, i64)>::new();
+        let mut stmt = self
+            .conn
+            .prepare("SELECT channel_id, message_id, data, created_at FROM response ORDER BY created_at")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let channel_id: i64 = row.get(0)?;
+            let message_id: i64 = row.get(1)?;
+            let data: String = row.get(2)?;
+            match serde_json::from_str::<RenderableResponse>(&data) {
+                Ok(response) => stored_responses.push(StoredResponse {
+                    channel_id: ChannelId::new(channel_id as u64),
+                    created_at: row.get(3)?,
+                    message_id: MessageId::new(message_id as u64),
+                    response,
+                }),
+                Err(err) => {
+                    eprintln!(
+                        "Discarding unreadable stored response {}/{}. {}",
+                        channel_id, message_id, err
+                    );
+                    unreadable.push((channel_id, message_id));
+                }
+            }
+        }
+        for (channel_id, message_id) in unreadable {
+            self.conn.execute(
+                "DELETE FROM response WHERE channel_id = ?1 AND message_id = ?2",
+                params![channel_id, message_id],
+            )?;
+        }
+        Ok(stored_responses)
+    }
+}
@@ -0,0 +1,70 @@
+//! This file implements storage for a log of searches and how they were answered.
+
+use rusqlite::{params, OptionalExtension};
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+
+use super::{unix_now, Store};
+use crate::search::RenderType;
+
+/// A single answered search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchLogEntry {
+    /// Channel the search was made in
+    pub channel_id: ChannelId,
+    /// Guild the search was made in, if not a DM
+    pub guild_id: Option<GuildId>,
+    /// Response message
+    pub message_id: MessageId,
+    /// The original query
+    pub query: String,
+    /// How the response was rendered
+    pub render_type: RenderType,
+    /// User who searched
+    pub user_id: UserId,
+}
+
+impl Store {
+    /// Records a search.
+    pub fn log_search(&self, entry: &SearchLogEntry) -> rusqlite::Result<()> {
+        let (render_type, guess) = match &entry.render_type {
+            RenderType::Category => ("category", None),
+            RenderType::Guess(Some(guess)) => ("guess", Some(guess)),
+            RenderType::Guess(None) => ("none", None),
+            RenderType::Invalid(_) => ("invalid", None),
+            RenderType::Result => ("result", None),
+        };
+        self.conn.execute(
+            "INSERT INTO search_log \
+             (channel_id, message_id, user_id, guild_id, query, render_type, guess, created_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                entry.channel_id.get() as i64,
+                entry.message_id.get() as i64,
+                entry.user_id.get() as i64,
+                entry.guild_id.map(|x| x.get() as i64),
+                entry.query,
+                render_type,
+                guess,
+                unix_now()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the query and guild of the search answered by a response message, if it was logged.
+    pub fn logged_search(
+        &self, channel_id: ChannelId, message_id: MessageId,
+    ) -> rusqlite::Result<Option<(String, Option<GuildId>)>> {
+        self.conn
+            .query_row(
+                "SELECT query, guild_id FROM search_log WHERE channel_id = ?1 AND message_id = ?2 \
+                 ORDER BY created_at DESC LIMIT 1",
+                params![channel_id.get() as i64, message_id.get() as i64],
+                |row| {
+                    let guild_id: Option<i64> = row.get(1)?;
+                    Ok((row.get(0)?, guild_id.map(|x| GuildId::new(x as u64))))
+                },
+            )
+            .optional()
+    }
+}
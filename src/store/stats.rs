@@ -0,0 +1,74 @@
+//! This file implements the usage counters shown by `?!pax about`.
+
+use rusqlite::params;
+use serenity::model::id::UserId;
+
+use super::{unix_now, Store};
+
+/// Usage counters since paxbot was first run.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    /// Total runs per command, most run first
+    pub commands: Vec<(String, u64)>,
+    /// Searches that found a result or category
+    pub search_hits: u64,
+    /// Total searches
+    pub searches: u64,
+    /// Distinct users who ran any command
+    pub users: u64,
+}
+
+impl Stats {
+    /// Returns the total number of commands run.
+    pub fn command_total(&self) -> u64 {
+        self.commands.iter().map(|(_, runs)| runs).sum()
+    }
+}
+
+impl Store {
+    /// Counts a command run, and the user who ran it.
+    pub fn record_command(&self, command: &str, user_id: UserId) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO command_count (command, runs) VALUES (?1, 1) \
+             ON CONFLICT (command) DO UPDATE SET runs = runs + 1",
+            params![command],
+        )?;
+        self.conn.execute(
+            "INSERT OR IGNORE INTO served_user (user_id, first_seen) VALUES (?1, ?2)",
+            params![user_id.get() as i64, unix_now()],
+        )?;
+        Ok(())
+    }
+
+    /// Counts a failed command run. The run itself is counted by [`Store::record_command`].
+    pub fn record_command_failure(&self, command: &str) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO command_count (command, failures) VALUES (?1, 1) \
+             ON CONFLICT (command) DO UPDATE SET failures = failures + 1",
+            params![command],
+        )?;
+        Ok(())
+    }
+
+    /// Reads all usage counters.
+    pub fn stats(&self) -> rusqlite::Result<Stats> {
+        let mut stats = Stats::default();
+        let mut stmt = self
+            .conn
+            .prepare("SELECT command, runs FROM command_count WHERE runs > 0 ORDER BY runs DESC, command")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            stats.commands.push((row.get(0)?, row.get::<_, i64>(1)?.max(0) as u64));
+        }
+        let (searches, search_hits): (i64, i64) = self.conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(render_type IN ('category', 'result')), 0) FROM search_log",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        stats.searches = searches.max(0) as u64;
+        stats.search_hits = search_hits.max(0) as u64;
+        let users: i64 = self.conn.query_row("SELECT COUNT(*) FROM served_user", [], |row| row.get(0))?;
+        stats.users = users.max(0) as u64;
+        Ok(stats)
+    }
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::fixture_backend;
+use paxbot::commands::slash::complete_query;
+use paxbot::search::backend::{SearchBackend, SearchBackendData, SearchBackendItem};
+
+fn item(name: &str) -> SearchBackendItem {
+    SearchBackendItem {
+        categories: Vec::new(),
+        ext_links: Vec::new(),
+        name: name.to_string(),
+        shortname: Vec::new(),
+        source: None,
+        text: String::new(),
+    }
+}
+
+#[test]
+fn prefix_matches_come_before_other_matches() {
+    let backend = fixture_backend();
+    assert_eq!(complete_query("t", backend.data()), vec!["T1", "Titan", "Ifrit"]);
+}
+
+#[test]
+fn suggests_category_names_ignoring_case() {
+    let backend = fixture_backend();
+    assert_eq!(complete_query("SAV", backend.data()), vec!["Savage Raids"]);
+    assert_eq!(complete_query("  gar ", backend.data()), vec!["Garuda"]);
+}
+
+#[test]
+fn unmatched_queries_suggest_nothing() {
+    let backend = fixture_backend();
+    assert!(complete_query("zzzz", backend.data()).is_empty());
+}
+
+#[test]
+fn empty_queries_suggest_every_name_once() {
+    let mut data = fixture_backend().data().clone();
+    data.search_results.push(item("Titan"));
+    assert_eq!(
+        complete_query("", &data),
+        vec!["Garuda", "Ifrit", "Savage Raids", "T1", "Titan"]
+    );
+}
+
+#[test]
+fn suggestions_are_capped_at_discord_limit() {
+    let data = SearchBackendData {
+        categories: Vec::new(),
+        search_results: (0..30).map(|x| item(&format!("Entry {:02}", x))).collect(),
+    };
+    let suggestions = complete_query("entry", &data);
+    assert_eq!(suggestions.len(), 25);
+    assert_eq!(suggestions.first().map(String::as_str), Some("Entry 00"));
+    assert_eq!(suggestions.last().map(String::as_str), Some("Entry 24"));
+}
+
+#[test]
+fn names_too_long_to_suggest_are_left_out() {
+    let data = SearchBackendData {
+        categories: Vec::new(),
+        search_results: vec![item(&"Long".repeat(26)), item("Longer")],
+    };
+    assert_eq!(complete_query("long", &data), vec!["Longer"]);
+}
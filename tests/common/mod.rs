@@ -0,0 +1,24 @@
+use std::{env, fs, path::PathBuf};
+
+use paxbot::search::backend::file::FileBackend;
+
+/// Loads the fixture content shared by the integration tests.
+pub fn fixture_backend() -> FileBackend {
+    FileBackend::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/content.toml")).expect("Fixture should load.")
+}
+
+/// Compares `actual` against the snapshot file `name` under `tests/snapshots`. Run with `PAXBOT_UPDATE_SNAPSHOTS=1` to
+/// write the current output as the new snapshot instead.
+#[allow(dead_code)]
+pub fn assert_snapshot(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(name);
+    if env::var_os("PAXBOT_UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, actual).expect("Snapshot should be writable.");
+        return;
+    }
+    let expected =
+        fs::read_to_string(&path).unwrap_or_else(|err| panic!("Failed to read snapshot {}: {}", path.display(), err));
+    assert_eq!(actual, expected, "Output differs from snapshot {}.", name);
+}
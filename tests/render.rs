@@ -0,0 +1,58 @@
+use paxbot::render::{embed::EmbedRenderer, json::JsonRenderer, text::TextRenderer, RenderableResponse};
+use paxbot::search::{self, backend::SearchBackend};
+
+mod common;
+use common::{assert_snapshot, fixture_backend};
+
+/// Queries covering each kind of response: a result with its category, a category listing, and no results.
+const QUERIES: &[(&str, &str)] = &[
+    ("result", "titan"),
+    ("category", "cat:\"Savage Raids\""),
+    ("none", "zzzz"),
+];
+
+async fn fixture_response(query: &str) -> RenderableResponse {
+    search::search(query, &fixture_backend())
+        .await
+        .get_renderable_response()
+}
+
+#[tokio::test]
+async fn embed_snapshots() {
+    for (name, query) in QUERIES {
+        let rendered = fixture_response(query)
+            .await
+            .render_with(&EmbedRenderer)
+            .into_iter()
+            .map(|(content, embed)| serde_json::json!({ "content": content, "embed": embed }))
+            .collect::<Vec<serde_json::Value>>();
+        let snapshot = serde_json::to_string_pretty(&rendered).expect("Embeds always serialize.");
+        assert_snapshot(&format!("embed_{}.json", name), &snapshot);
+    }
+}
+
+#[tokio::test]
+async fn text_snapshots() {
+    for (name, query) in QUERIES {
+        let snapshot = fixture_response(query)
+            .await
+            .render_with(&TextRenderer)
+            .join("\n\n---\n\n");
+        assert_snapshot(&format!("text_{}.txt", name), &snapshot);
+    }
+}
+
+#[tokio::test]
+async fn json_snapshots() {
+    for (name, query) in QUERIES {
+        let rendered = fixture_response(query).await.render_with(&JsonRenderer);
+        let snapshot = serde_json::to_string_pretty(&rendered).expect("JSON snapshots always serialize.");
+        assert_snapshot(&format!("json_{}.json", name), &snapshot);
+    }
+}
+
+#[test]
+fn fixture_passes_content_check() {
+    let issues = paxbot::lint::lint(fixture_backend().data());
+    assert!(issues.is_empty(), "{:?}", issues);
+}
@@ -0,0 +1,125 @@
+use paxbot::render::Navigation;
+use paxbot::search::{self, RenderType, SearchResponse, SearchResult};
+
+mod common;
+use common::fixture_backend;
+
+#[tokio::test]
+async fn name_hits_beat_body_hits() {
+    let backend = fixture_backend();
+    let response = search::search("titan", &backend).await;
+    assert_eq!(response.render_type, RenderType::Result);
+    let names = response.results.iter().map(|x| x.name.as_str()).collect::<Vec<&str>>();
+    assert_eq!(names, vec!["Titan", "Garuda"]);
+    assert!(response.results[0].score > response.results[1].score);
+}
+
+#[tokio::test]
+async fn body_only_hits_are_returned() {
+    let backend = fixture_backend();
+    let response = search::search("pillar", &backend).await;
+    assert_eq!(response.results.first().map(|x| x.name.as_str()), Some("Garuda"));
+}
+
+#[tokio::test]
+async fn results_link_to_matched_categories() {
+    let backend = fixture_backend();
+    let response = search::search("cat:\"Savage Raids\"", &backend).await;
+    let messages = response.get_renderable_messages();
+    assert_eq!(messages[0].result.as_deref(), Some("Savage Raids"));
+    assert!(messages[1..].iter().all(|x| x.category_index == Some(0)));
+}
+
+#[tokio::test]
+async fn unmatched_categories_are_not_linked() {
+    let backend = fixture_backend();
+    let response = search::search("titan", &backend).await;
+    assert!(response.category_results.is_empty());
+    let renderable_response = response.get_renderable_response();
+    assert!(renderable_response
+        .messages()
+        .iter()
+        .all(|x| x.category_index.is_none()));
+    assert_eq!(renderable_response.target(0, Navigation::Category), None);
+}
+
+#[tokio::test]
+async fn siblings_that_do_not_match_are_left_out() {
+    let backend = fixture_backend();
+    let response = search::search("titan", &backend).await;
+    assert!(response.member_results.is_empty());
+    let renderable_response = response.get_renderable_response();
+    assert_eq!(renderable_response.messages().len(), 2);
+    assert!(renderable_response
+        .messages()
+        .iter()
+        .all(|x| x.result.as_deref() != Some("Ifrit")));
+}
+
+#[tokio::test]
+async fn matched_categories_link_members_without_counting_them() {
+    let backend = fixture_backend();
+    let response = search::search("savage raids", &backend).await;
+    assert_eq!(response.render_type, RenderType::Category);
+    assert!(response.results.is_empty());
+    assert_eq!(response.member_results.len(), 3);
+    let renderable_response = response.get_renderable_response();
+    let messages = renderable_response.messages();
+    assert_eq!(messages[0].members, vec![1, 2, 3]);
+    assert!(messages[1..]
+        .iter()
+        .all(|x| x.member_only && x.category_index == Some(0)));
+    let category_footer = messages[0].embed.as_ref().and_then(|x| x.footer.as_deref()).unwrap();
+    assert!(!category_footer.contains("Displaying result"));
+    // Member pages are only reached from their category page, and lead back to it.
+    assert_eq!(renderable_response.target(0, Navigation::Forward), Some(0));
+    assert_eq!(renderable_response.target(0, Navigation::Last), Some(0));
+    assert_eq!(renderable_response.target(0, Navigation::Member(2)), Some(3));
+    assert_eq!(renderable_response.target(3, Navigation::Back), Some(0));
+}
+
+#[tokio::test]
+async fn name_and_phrase_queries_do_not_add_members() {
+    let backend = fixture_backend();
+    for query in &["name:\"Savage Raids\"", "\"savage raids\""] {
+        let response = search::search(query, &backend).await;
+        assert_eq!(response.category_results.len(), 1, "{}", query);
+        assert!(response.member_results.is_empty(), "{}", query);
+    }
+}
+
+#[test]
+fn split_results_are_numbered_after_splitting() {
+    let result = |name: &str, text: String| SearchResult {
+        categories: Vec::new(),
+        ext_links: Vec::new(),
+        name: name.to_string(),
+        score: 1f32,
+        shortname: Vec::new(),
+        text,
+    };
+    let response = SearchResponse {
+        category_results: Vec::new(),
+        member_results: Vec::new(),
+        query: String::from("titan"),
+        render_type: RenderType::Result,
+        results: vec![
+            result("Titan", "Titan jumps. ".repeat(1000)),
+            result("Garuda", String::from("Garuda flies.")),
+        ],
+    };
+    let renderable_response = response.get_renderable_response();
+    let footers = renderable_response
+        .messages()
+        .iter()
+        .map(|x| x.embed.as_ref().and_then(|x| x.footer.as_deref()).unwrap_or_default())
+        .collect::<Vec<&str>>();
+    assert_eq!(footers.len(), 4);
+    for (index, footer) in footers.iter().enumerate() {
+        assert!(
+            footer.starts_with(&format!("Displaying result {} of 4.", index + 1)),
+            "{}",
+            footer
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use paxbot::search::{
+    self,
+    backend::{sqlite::SqliteBackend, SearchBackend, SearchBackendData},
+    SearchResponse,
+};
+
+mod common;
+use common::fixture_backend;
+
+/// Imports the fixture content into a fresh in-memory database.
+fn sqlite_backend() -> SqliteBackend {
+    let mut backend = SqliteBackend::new(":memory:").expect("In-memory database should open.");
+    backend
+        .import(fixture_backend().data())
+        .expect("Fixture should import.");
+    backend
+}
+
+/// Category names and texts, in order.
+fn categories(data: &SearchBackendData) -> Vec<(&str, &str)> {
+    data.categories
+        .iter()
+        .map(|x| (x.name.as_str(), x.text.as_str()))
+        .collect()
+}
+
+/// Result, category and member names, in order. Body text scores come from each backend's own BM25, so only the
+/// ranking is compared.
+fn names(response: &SearchResponse) -> (Vec<&str>, Vec<&str>, Vec<&str>) {
+    (
+        response.results.iter().map(|x| x.name.as_str()).collect(),
+        response.category_results.iter().map(|x| x.name.as_str()).collect(),
+        response.member_results.iter().map(|x| x.name.as_str()).collect(),
+    )
+}
+
+#[test]
+fn import_round_trips_the_fixture() {
+    let file_backend = fixture_backend();
+    let sqlite_backend = sqlite_backend();
+    let (expected, actual) = (file_backend.data(), sqlite_backend.data());
+    assert_eq!(categories(actual), categories(expected));
+    assert_eq!(actual.search_results.len(), expected.search_results.len());
+    for (actual, expected) in actual.search_results.iter().zip(&expected.search_results) {
+        assert_eq!(actual.name, expected.name);
+        assert_eq!(actual.shortname, expected.shortname);
+        assert_eq!(actual.ext_links, expected.ext_links);
+        assert_eq!(actual.categories, expected.categories);
+        assert_eq!(actual.text, expected.text);
+    }
+}
+
+#[tokio::test]
+async fn searches_match_the_toml_backend() {
+    let file_backend = fixture_backend();
+    let sqlite_backend = sqlite_backend();
+    // Terms found in half the fixture, like "titan", are left out: FTS5 floors their weight to almost nothing, while
+    // the in-memory index still counts them.
+    let queries = [
+        "T1",
+        "ifrit",
+        "pillar",
+        "pillar wind",
+        "spread landing",
+        "raid",
+        "savage",
+        "cat:\"Savage Raids\"",
+        "nothing matches this",
+    ];
+    for query in &queries {
+        let expected = search::search(query, &file_backend).await;
+        let actual = search::search(query, &sqlite_backend).await;
+        assert_eq!(actual.render_type, expected.render_type, "Query: {}", query);
+        assert_eq!(names(&actual), names(&expected), "Query: {}", query);
+    }
+}